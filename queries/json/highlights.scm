@@ -0,0 +1,13 @@
+; JSON highlights.
+
+(comment) @comment
+
+(pair key: (string) @property)
+(string) @string
+(escape_sequence) @string.escape
+(number) @number
+[(true) (false)] @boolean
+(null) @constant.builtin
+
+["," ":"] @punctuation.delimiter
+["[" "]" "{" "}"] @punctuation.bracket
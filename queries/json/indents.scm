@@ -0,0 +1,11 @@
+[
+  (object)
+  (array)
+] @indent
+
+[
+  "}"
+  "]"
+] @outdent
+
+(string) @ignore
@@ -0,0 +1,21 @@
+; Markdown inline markup: emphasis, code spans and links.
+
+(code_span) @markup.raw
+(code_span_delimiter) @punctuation.delimiter
+(emphasis) @markup.italic
+(strong_emphasis) @markup.bold
+(strikethrough) @markup.strikethrough
+(emphasis_delimiter) @punctuation.delimiter
+
+[
+  (link_destination)
+  (uri_autolink)
+  (email_autolink)
+] @markup.link.url
+[
+  (link_text)
+  (link_label)
+  (image_description)
+] @markup.link.label
+
+(backslash_escape) @string.escape
@@ -0,0 +1,7 @@
+[
+  (section)
+  (fenced_code_block)
+  (list)
+  (block_quote)
+  (html_block)
+] @fold
@@ -0,0 +1,39 @@
+; Markdown block structure. Inline markup (emphasis, code spans, links) lives
+; in a separate grammar and is not highlighted here.
+
+(atx_heading) @markup.heading
+(setext_heading) @markup.heading
+[
+  (atx_h1_marker)
+  (atx_h2_marker)
+  (atx_h3_marker)
+  (atx_h4_marker)
+  (atx_h5_marker)
+  (atx_h6_marker)
+  (setext_h1_underline)
+  (setext_h2_underline)
+] @punctuation.special
+
+(fenced_code_block_delimiter) @punctuation.delimiter
+(info_string) @label
+(fenced_code_block) @markup.raw
+(indented_code_block) @markup.raw
+
+(link_destination) @markup.link.url
+(link_label) @markup.link.label
+(link_title) @string
+
+[
+  (list_marker_plus)
+  (list_marker_minus)
+  (list_marker_star)
+  (list_marker_dot)
+  (list_marker_parenthesis)
+] @markup.list
+(task_list_marker_checked) @markup.list
+(task_list_marker_unchecked) @markup.list
+(thematic_break) @punctuation.special
+
+(block_quote) @markup.quote
+(block_quote_marker) @punctuation.special
+(backslash_escape) @string.escape
@@ -0,0 +1,8 @@
+; Fenced code takes the language named by its info string. Fences without one
+; use the language of the enclosing document, e.g. Rust in doc comments.
+(fenced_code_block
+  (info_string (language) @injection.language)?
+  (code_fence_content) @injection.content)
+
+((inline) @injection.content
+ (#set! injection.language "markdown-inline"))
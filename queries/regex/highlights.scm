@@ -0,0 +1,45 @@
+; Regular expressions, mostly injected into string literals.
+
+(pattern_character) @string
+[
+  (class_character)
+  (posix_class_name)
+] @constant.character
+(any_character) @operator
+
+[
+  (identity_escape)
+  (control_letter_escape)
+  (character_class_escape)
+  (control_escape)
+  (start_assertion)
+  (end_assertion)
+  (boundary_assertion)
+  (non_boundary_assertion)
+] @string.escape
+
+(group_name) @property
+(flags) @keyword
+(count_quantifier (decimal_digits) @number)
+(count_quantifier "," @punctuation.delimiter)
+(character_class "^" @operator)
+(class_range "-" @operator)
+
+["*" "+" "?" "|" "=" "!"] @operator
+
+[
+  "("
+  ")"
+  "(?"
+  "(?:"
+  "(?<"
+  "(?P<"
+  "(?P="
+  ">"
+  "["
+  "]"
+  "{"
+  "}"
+  "[:"
+  ":]"
+] @punctuation.bracket
@@ -0,0 +1,24 @@
+; Constructs shown in the breadcrumb and the sticky headers, besides the
+; symbols of the outline. They are labelled with their `label` property, or
+; with the text of their `@label` capture.
+
+((match_expression) @context
+  (#set! label "match"))
+
+(match_arm
+  pattern: (_) @label) @context
+
+((if_expression) @context
+  (#set! label "if"))
+
+((for_expression) @context
+  (#set! label "for"))
+
+((while_expression) @context
+  (#set! label "while"))
+
+((loop_expression) @context
+  (#set! label "loop"))
+
+((closure_expression) @context
+  (#set! label "closure"))
@@ -0,0 +1,31 @@
+; Regions that can be folded. `@fold` nodes fold on their own; `@fold.run`
+; nodes fold together with neighbours of the same kind on the next lines.
+
+[
+  (mod_item)
+  (function_item)
+  (impl_item)
+  (trait_item)
+  (struct_item)
+  (enum_item)
+  (union_item)
+  (macro_definition)
+  (const_item)
+  (static_item)
+  (block)
+  (match_block)
+  (match_arm)
+  (closure_expression)
+  (use_list)
+  (field_declaration_list)
+  (field_initializer_list)
+  (array_expression)
+  (arguments)
+  (block_comment)
+] @fold
+
+[
+  (line_comment)
+  (use_declaration)
+  (attribute_item)
+] @fold.run
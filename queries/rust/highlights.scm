@@ -0,0 +1,178 @@
+; Rust highlights.
+;
+; When several patterns capture the same node the first one wins, and inner
+; nodes are drawn over the nodes that contain them, so specific patterns go
+; first and the catch-all identifier rules come last.
+
+; Comments
+
+(line_comment (doc_comment)) @comment.documentation
+(block_comment (doc_comment)) @comment.documentation
+(line_comment) @comment
+(block_comment) @comment
+
+; Literals
+
+(string_literal) @string
+(raw_string_literal) @string
+(char_literal) @string
+(escape_sequence) @string.escape
+(integer_literal) @number
+(float_literal) @number
+(boolean_literal) @boolean
+
+; Attributes
+
+(attribute_item) @attribute
+(inner_attribute_item) @attribute
+(attribute (identifier) @attribute)
+(attribute (scoped_identifier name: (identifier) @attribute))
+
+; Macros
+
+(macro_invocation
+  macro: (identifier) @function.macro
+  "!" @function.macro)
+(macro_invocation
+  macro: (scoped_identifier name: (identifier) @function.macro)
+  "!" @function.macro)
+(macro_definition name: (identifier) @function.macro)
+(metavariable) @variable.parameter
+(fragment_specifier) @type.builtin
+(token_repetition_pattern "$" @punctuation.special)
+(token_repetition "$" @punctuation.special)
+
+; Definitions
+
+(function_item name: (identifier) @function)
+(function_signature_item name: (identifier) @function)
+(enum_variant name: (identifier) @constructor)
+(const_item name: (identifier) @constant)
+(static_item name: (identifier) @constant)
+(mod_item name: (identifier) @module)
+(parameter pattern: (identifier) @variable.parameter)
+(closure_parameters (identifier) @variable.parameter)
+(lifetime) @label
+(label) @label
+
+; Calls
+
+(call_expression
+  function: (identifier) @function)
+(call_expression
+  function: (field_expression
+    field: (field_identifier) @function.method))
+(call_expression
+  function: (scoped_identifier
+    name: (identifier) @function))
+(generic_function
+  function: (identifier) @function)
+(generic_function
+  function: (scoped_identifier
+    name: (identifier) @function))
+(generic_function
+  function: (field_expression
+    field: (field_identifier) @function.method))
+
+; Paths
+
+((scoped_identifier
+  path: (identifier) @type)
+ (#match? @type "^[A-Z]"))
+((scoped_type_identifier
+  path: (identifier) @type)
+ (#match? @type "^[A-Z]"))
+(scoped_identifier path: (identifier) @module)
+(scoped_type_identifier path: (identifier) @module)
+(scoped_use_list path: (identifier) @module)
+(use_declaration argument: (identifier) @module)
+(crate) @module
+(super) @module
+
+; Types
+
+(primitive_type) @type.builtin
+(type_identifier) @type
+((identifier) @constant
+ (#match? @constant "^[A-Z][A-Z0-9_]+$"))
+((identifier) @constructor
+ (#match? @constructor "^[A-Z]"))
+(struct_pattern
+  type: (scoped_type_identifier
+    name: (type_identifier) @constructor))
+
+; Fields
+
+(field_identifier) @property
+(shorthand_field_identifier) @property
+
+; Keywords
+
+(self) @variable.builtin
+(mutable_specifier) @keyword
+(visibility_modifier) @keyword
+
+[
+  "break"
+  "continue"
+  "else"
+  "for"
+  "if"
+  "in"
+  "loop"
+  "match"
+  "return"
+  "while"
+  "yield"
+  "await"
+] @keyword.control
+
+[
+  "as"
+  "async"
+  "const"
+  "default"
+  "dyn"
+  "enum"
+  "extern"
+  "fn"
+  "gen"
+  "impl"
+  "let"
+  "macro_rules!"
+  "mod"
+  "move"
+  "pub"
+  "raw"
+  "ref"
+  "static"
+  "struct"
+  "trait"
+  "type"
+  "union"
+  "unsafe"
+  "use"
+  "where"
+] @keyword
+
+; Punctuation
+
+["(" ")" "[" "]" "{" "}"] @punctuation.bracket
+(type_arguments ["<" ">"] @punctuation.bracket)
+(type_parameters ["<" ">"] @punctuation.bracket)
+["::" ":" "." "," ";"] @punctuation.delimiter
+; Operators are matched through their parent so the `/` and `!` markers of
+; doc comments stay comment-coloured.
+(binary_expression operator: _ @operator)
+(compound_assignment_expr operator: _ @operator)
+(unary_expression ["-" "*" "!"] @operator)
+(reference_expression "&" @operator)
+(reference_type "&" @operator)
+(pointer_type "*" @operator)
+(range_expression [".." "..=" "..."] @operator)
+(try_expression "?" @operator)
+["->" "=>" "="] @operator
+
+; Everything else
+
+(identifier) @variable
@@ -0,0 +1,90 @@
+; Indentation rules. Lines inside an `@indent` node that started on an
+; earlier line are indented one level deeper than that line, or line up with
+; the last line of its `@indent.anchor` when the anchor spans several lines.
+; A line opening with an `@outdent` node lines up with the line its parent
+; started on. Lines starting inside an `@ignore` node keep their indentation.
+
+[
+  (block)
+  (match_block)
+  (match_arm)
+  (declaration_list)
+  (field_declaration_list)
+  (field_initializer_list)
+  (enum_variant_list)
+  (ordered_field_declaration_list)
+  (use_list)
+  (arguments)
+  (parameters)
+  (closure_parameters)
+  (type_arguments)
+  (type_parameters)
+  (tuple_expression)
+  (tuple_pattern)
+  (tuple_type)
+  (array_expression)
+  (struct_pattern)
+  (token_tree)
+  (macro_definition)
+  (where_clause)
+  (let_chain)
+  (let_declaration)
+  (const_item)
+  (static_item)
+  (assignment_expression)
+  (compound_assignment_expr)
+  (binary_expression)
+  (call_expression)
+  (field_expression)
+  (return_expression)
+] @indent
+
+; Chained calls line up with the end of a multi-line receiver.
+(field_expression
+  value: (_) @indent.anchor) @indent
+
+[
+  "}"
+  ")"
+  "]"
+] @outdent
+
+(let_declaration
+  "else" @outdent)
+
+; Bodies opened on a line of their own, e.g. after a `where` clause.
+(function_item
+  body: (block) @outdent)
+
+(impl_item
+  body: (declaration_list) @outdent)
+
+(trait_item
+  body: (declaration_list) @outdent)
+
+(struct_item
+  body: (field_declaration_list) @outdent)
+
+(enum_item
+  body: (enum_variant_list) @outdent)
+
+(if_expression
+  consequence: (block) @outdent)
+
+(while_expression
+  body: (block) @outdent)
+
+(for_expression
+  body: (block) @outdent)
+
+(match_expression
+  body: (match_block) @outdent)
+
+(match_arm
+  value: (block) @outdent)
+
+[
+  (string_literal)
+  (raw_string_literal)
+  (block_comment)
+] @ignore
@@ -0,0 +1,31 @@
+; Doc comments are Markdown. A run of consecutive `///` (or `//!`) lines is
+; parsed as one document.
+((line_comment
+   outer: (outer_doc_comment_marker)
+   doc: (doc_comment) @injection.content)
+ (#set! injection.language "markdown")
+ (#set! injection.combined))
+
+((line_comment
+   inner: (inner_doc_comment_marker)
+   doc: (doc_comment) @injection.content)
+ (#set! injection.language "markdown")
+ (#set! injection.combined))
+
+; Patterns passed to the regex crate constructors.
+((call_expression
+   function: (scoped_identifier
+     path: [
+       (identifier) @_type
+       (scoped_identifier name: (identifier) @_type)
+     ]
+     name: (identifier) @_method)
+   arguments: (arguments
+     .
+     [
+       (string_literal (string_content) @injection.content)
+       (raw_string_literal (string_content) @injection.content)
+     ]))
+ (#any-of? @_type "Regex" "RegexBuilder" "RegexSet" "RegexSetBuilder")
+ (#eq? @_method "new")
+ (#set! injection.language "regex"))
@@ -0,0 +1,18 @@
+; Symbols listed in the outline. The pattern's other capture names the kind
+; of symbol; its `@name` captures, and the text between them, label it.
+
+(mod_item name: (_) @name) @module
+(function_item name: (_) @name) @function
+(function_signature_item name: (_) @name) @function
+(struct_item name: (_) @name) @struct
+(union_item name: (_) @name) @union
+(enum_item name: (_) @name) @enum
+(trait_item name: (_) @name) @trait
+(type_item name: (_) @name) @type
+(const_item name: (_) @name) @const
+(static_item name: (_) @name) @static
+(macro_definition name: (_) @name) @macro
+
+(impl_item
+  trait: (_)? @name
+  type: (_) @name) @impl
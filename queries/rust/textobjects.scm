@@ -0,0 +1,48 @@
+; Text objects and motion targets. `@<object>.around` is the whole
+; construct and `@<object>.inside` its contents; brackets, quotes and comment
+; markers at the ends of an `.inside` node are left out.
+
+(function_item
+  body: (block) @function.inside) @function.around
+
+(function_signature_item) @function.around
+
+(closure_expression
+  body: (block) @function.inside) @function.around
+
+(impl_item
+  body: (declaration_list) @impl.inside) @impl.around
+
+(struct_item
+  body: (field_declaration_list)? @struct.inside) @struct.around
+
+; Functions marked `#[test]`, `#[tokio::test]` and the like, possibly with
+; more attributes such as `#[should_panic]` in between.
+((attribute_item
+  (attribute
+    [
+      (identifier) @_test
+      (scoped_identifier name: (identifier) @_test)
+    ]))
+  .
+  (attribute_item)*
+  .
+  (function_item
+    body: (block) @test.inside) @test.around
+  (#eq? @_test "test"))
+
+; Arguments and parameters; "around" also takes the comma next to them.
+(parameters (_) @parameter.around)
+(closure_parameters (_) @parameter.around)
+(type_parameters (_) @parameter.around)
+(arguments (_) @parameter.around)
+(type_arguments (_) @parameter.around)
+
+(string_literal) @string.inside @string.around
+
+(raw_string_literal
+  (string_content) @string.inside) @string.around
+
+(block_comment) @comment.inside @comment.around
+
+(line_comment) @comment.around
@@ -0,0 +1,8 @@
+[
+  (table)
+  (table_array_element)
+  (array)
+  (inline_table)
+] @fold
+
+(comment) @fold.run
@@ -0,0 +1,35 @@
+; TOML highlights. Same precedence rules as the Rust query: first pattern
+; wins for a node, inner nodes are drawn over outer ones.
+
+(comment) @comment
+
+; Tables and keys
+
+(table (bare_key) @type)
+(table (dotted_key (bare_key) @type))
+(table (quoted_key) @type)
+(table_array_element (bare_key) @type)
+(table_array_element (dotted_key (bare_key) @type))
+(table_array_element (quoted_key) @type)
+(bare_key) @property
+(quoted_key) @property
+
+; Values
+
+(string) @string
+(escape_sequence) @string.escape
+(integer) @number
+(float) @number
+(boolean) @boolean
+[
+  (offset_date_time)
+  (local_date_time)
+  (local_date)
+  (local_time)
+] @string.special
+
+; Punctuation
+
+"=" @operator
+["." ","] @punctuation.delimiter
+["[" "]" "[[" "]]" "{" "}"] @punctuation.bracket
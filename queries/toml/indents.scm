@@ -0,0 +1,11 @@
+[
+  (array)
+  (inline_table)
+] @indent
+
+[
+  "}"
+  "]"
+] @outdent
+
+(string) @ignore
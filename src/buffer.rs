@@ -1,21 +1,62 @@
 use ropey::Rope;
-use std::path::{Path, PathBuf};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use tree_sitter::{InputEdit, Point};
 
-use crate::highlighter::Highlighter;
+use crate::{
+    column, context,
+    fold::Folds,
+    highlighter::Highlighter,
+    history::{Edit, EditKind, History},
+    indent::{self, Indent, IndentStyle},
+    language,
+    register::Register,
+    textobject,
+    theme::Theme,
+};
 
 pub struct Buffer {
     pub text: Rope,
     pub cursor_x: usize,
     pub cursor_y: usize,
     pub scroll_y: usize,
+    /// Visual line of the wrapped row `scroll_y` shown at the top.
+    pub scroll_subline: usize,
+    /// Display column shown at the left edge when lines aren't wrapped.
+    pub scroll_x: usize,
+    /// Wrap long lines onto several screen lines instead of scrolling
+    /// sideways.
+    pub wrap: bool,
+    /// Other end of the visual selection, as `(x, y)`.
+    pub anchor: Option<(usize, usize)>,
     pub filepath: Option<PathBuf>,
     pub name: String,
     pub modified: bool,
+    /// Bumped on every text change, so derived state can tell it is stale.
+    pub revision: u64,
     pub highlighter: Highlighter,
+    pub history: History,
+    pub folds: Folds,
+    /// How lines are indented, from `.editorconfig` or the file contents.
+    pub indent: IndentStyle,
+    /// Selections replaced by `expand_selection`, innermost first.
+    expansions: Vec<Expansion>,
+    /// Columns of text on a screen line, as of the last `compute_scroll`.
+    view_width: usize,
+}
+
+/// A selection grown to a syntax node, and what it was before.
+struct Expansion {
+    anchor: Option<(usize, usize)>,
+    cursor: (usize, usize),
+    /// Char range selected by the expansion.
+    to: (usize, usize),
 }
 
 impl Buffer {
-    pub fn from_file(path: &Path) -> Self {
+    pub fn from_file(path: &Path, theme: &Theme) -> Self {
         let text = std::fs::read_to_string(path)
             .map(|s| Rope::from_str(&s))
             .unwrap_or_else(|_| Rope::new());
@@ -25,18 +66,32 @@ impl Buffer {
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| path.to_string_lossy().to_string());
 
-        let mut highlighter = Highlighter::new();
-        highlighter.update(&text.to_string());
+        let mut highlighter = Highlighter::new(language::detect(path, &text), theme);
+        highlighter.update(&text);
+        let mut folds = Folds::default();
+        folds.refresh(&highlighter, &text, 0);
+        let mut history = History::restore(path, &text).unwrap_or_default();
+        history.mark_saved();
 
         Self {
-            text,
             cursor_x: 0,
             cursor_y: 0,
             scroll_y: 0,
+            scroll_subline: 0,
+            scroll_x: 0,
+            wrap: false,
+            anchor: None,
             filepath: Some(path.to_path_buf()),
             name,
             modified: false,
+            revision: 0,
             highlighter,
+            history,
+            folds,
+            indent: IndentStyle::for_file(path, &text),
+            expansions: Vec::new(),
+            view_width: 0,
+            text,
         }
     }
 
@@ -44,6 +99,19 @@ impl Buffer {
         if let Some(ref path) = self.filepath {
             std::fs::write(path, self.text.to_string())?;
             self.modified = false;
+            self.history.mark_saved();
+            // The file is written; a stale undo cache isn't worth failing over.
+            let _ = self.persist_history();
+        }
+        Ok(())
+    }
+
+    /// Store the undo history in the state directory's undo cache so
+    /// reopening the unchanged file restores it. Edits made since the last
+    /// save are dropped, since they aren't in the file on disk.
+    pub fn persist_history(&self) -> anyhow::Result<()> {
+        if let Some(path) = &self.filepath {
+            self.history.persist(path, &self.text)?;
         }
         Ok(())
     }
@@ -58,99 +126,568 @@ impl Buffer {
 
     pub fn move_left(&mut self) {
         if self.cursor_x > 0 {
-            self.cursor_x -= 1;
+            self.cursor_x = column::prev_grapheme(&self.line_content(self.cursor_y), self.cursor_x);
         } else if self.cursor_y > 0 {
-            self.cursor_y -= 1;
+            self.cursor_y = self.folds.prev_visible(self.cursor_y);
             self.cursor_x = self.visible_line_len(self.cursor_y);
         }
     }
 
     pub fn move_right(&mut self) {
         let vis_len = self.visible_line_len(self.cursor_y);
+        let next = self.folds.next_visible(self.cursor_y);
         if self.cursor_x < vis_len {
-            self.cursor_x += 1;
-        } else if self.cursor_y + 1 < self.text.len_lines() {
-            self.cursor_y += 1;
+            let next = column::next_grapheme(&self.line_content(self.cursor_y), self.cursor_x);
+            self.cursor_x = next.clamp(self.cursor_x + 1, vis_len);
+        } else if next < self.text.len_lines() {
+            self.cursor_y = next;
             self.cursor_x = 0;
         }
     }
 
+    /// Move up `scroll` visual lines, keeping the display column; a closed
+    /// fold counts as one, and a wrapped row as one per screen line.
     pub fn move_up(&mut self, scroll: usize) {
-        let jump = self.cursor_y.min(scroll);
-        if jump > 0 {
-            self.cursor_y -= jump;
-            self.cursor_x = self.cursor_x.min(self.visible_line_len(self.cursor_y));
-        } else if self.cursor_y == 0 {
+        let start = (self.cursor_y, self.cursor_subline());
+        let mut pos = start;
+        for _ in 0..scroll {
+            match self.prev_visual(pos) {
+                Some(prev) => pos = prev,
+                None => break,
+            }
+        }
+        if pos < start {
+            self.move_to_visual(pos);
+        } else if scroll > 0 {
             self.cursor_x = 0;
         }
     }
 
+    /// Move down `scroll` visual lines, keeping the display column; a closed
+    /// fold counts as one, and a wrapped row as one per screen line.
     pub fn move_down(&mut self, scroll: usize) {
-        let nb_lines = self.text.len_lines();
-        let jump = (nb_lines - 1 - self.cursor_y).min(scroll);
-        if jump > 0 {
-            self.cursor_y += jump;
-            self.cursor_x = self.cursor_x.min(self.visible_line_len(self.cursor_y));
-        } else if self.cursor_y + 1 == nb_lines {
+        let start = (self.cursor_y, self.cursor_subline());
+        let mut pos = start;
+        for _ in 0..scroll {
+            match self.next_visual(pos) {
+                Some(next) => pos = next,
+                None => break,
+            }
+        }
+        if pos > start {
+            self.move_to_visual(pos);
+        } else if scroll > 0 {
             self.cursor_x = self.visible_line_len(self.cursor_y);
         }
     }
 
+    /// Put the cursor on visual line `sub` of `row`, as far into it as it
+    /// is into its current one.
+    fn move_to_visual(&mut self, (row, sub): (usize, usize)) {
+        let starts = self.wrap_starts(self.cursor_y);
+        let offset = self.cursor_column() - starts[self.cursor_subline()].1;
+        let col = self.wrap_starts(row).get(sub).map_or(0, |&(_, col)| col);
+        self.cursor_y = row;
+        self.cursor_x = self.char_at_visual(row, sub, col + offset);
+    }
+
     pub fn on_text_changed(&mut self) {
         self.modified = true;
-        self.highlighter.update(&self.text.to_string());
+        self.revision += 1;
+        self.highlighter.update(&self.text);
+    }
+
+    /// Bring the fold regions up to date with the text. Edits only shift
+    /// them, so this runs once before they are drawn rather than per edit.
+    pub fn refresh_folds(&mut self) {
+        self.folds
+            .refresh(&self.highlighter, &self.text, self.revision);
+    }
+
+    /// Apply `edit` to the rope, record it in the history and move the cursor
+    /// to `cursor_after`.
+    fn apply_edit(&mut self, edit: Edit, kind: EditKind, cursor_after: (usize, usize)) {
+        let cursor_before = (self.cursor_x, self.cursor_y);
+        self.splice(&edit);
+        self.history.record(edit, kind, cursor_before, cursor_after);
+        (self.cursor_x, self.cursor_y) = cursor_after;
+        self.on_text_changed();
+    }
+
+    /// Replace each char range `(start, end, text)` as a single undo step,
+    /// reparsing once at the end. Ranges come back to front, so the positions
+    /// of the ones still to apply stay valid.
+    fn splice_ranges(&mut self, ranges: impl IntoIterator<Item = (usize, usize, String)>) {
+        let cursor = (self.cursor_x, self.cursor_y);
+        let mut changed = false;
+        self.history.begin_group();
+        for (start, end, text) in ranges {
+            let edit = self.edit_at(start, end, &text);
+            self.splice(&edit);
+            self.history.record(edit, EditKind::Other, cursor, cursor);
+            changed = true;
+        }
+        self.history.end_group();
+        if changed {
+            self.on_text_changed();
+        }
+    }
+
+    /// Apply `edit` to the rope and report it to the highlighter, so the
+    /// next reparse only has to look at what changed.
+    fn splice(&mut self, edit: &Edit) {
+        let removed_len = edit.removed.chars().count();
+        let start_byte = self.text.char_to_byte(edit.pos);
+        let old_end_byte = self.text.char_to_byte(edit.pos + removed_len);
+        let start_position = self.byte_point(start_byte);
+        let old_end_position = self.byte_point(old_end_byte);
+
+        self.text.remove(edit.pos..edit.pos + removed_len);
+        self.text.insert(edit.pos, &edit.inserted);
+
+        let new_end_byte = start_byte + edit.inserted.len();
+        let new_end_position = self.byte_point(new_end_byte);
+        self.highlighter.edit(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position,
+        });
+        self.folds.shift(
+            start_position.row,
+            old_end_position.row,
+            new_end_position.row,
+        );
+    }
+
+    /// Tree-sitter position (row, byte column) of a byte offset.
+    fn byte_point(&self, byte: usize) -> Point {
+        let row = self.text.byte_to_line(byte);
+        Point::new(row, byte - self.text.line_to_byte(row))
     }
 
+    /// Build an edit replacing the chars in `start..end` with `inserted`.
+    fn edit_at(&self, start: usize, end: usize, inserted: &str) -> Edit {
+        Edit {
+            pos: start,
+            removed: self.text.slice(start..end).to_string(),
+            inserted: inserted.to_string(),
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(tx) = self.history.undo() else {
+            return false;
+        };
+        for edit in tx.edits.iter().rev() {
+            self.splice(&edit.inverse());
+        }
+        (self.cursor_x, self.cursor_y) = tx.cursor_before;
+        self.on_text_changed();
+        self.modified = !self.history.is_saved();
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(tx) = self.history.redo() else {
+            return false;
+        };
+        for edit in &tx.edits {
+            self.splice(edit);
+        }
+        (self.cursor_x, self.cursor_y) = tx.cursor_after;
+        self.on_text_changed();
+        self.modified = !self.history.is_saved();
+        true
+    }
+
+    /// Insert `c` at the cursor. A closing bracket typed first on a line
+    /// moves the line back to the level of the line it closes.
     pub fn insert_char(&mut self, c: char) {
         let pos = self.text.line_to_char(self.cursor_y) + self.cursor_x;
-        self.text.insert_char(pos, c);
-        self.cursor_x += 1;
-        self.on_text_changed();
+        let blank_before = self
+            .text
+            .slice(self.text.line_to_char(self.cursor_y)..pos)
+            .chars()
+            .all(|c| c == ' ' || c == '\t');
+        let edit = self.edit_at(pos, pos, &c.to_string());
+        self.apply_edit(edit, EditKind::Insert, (self.cursor_x + 1, self.cursor_y));
+
+        if blank_before && matches!(c, '}' | ')' | ']') {
+            self.reindent_line(self.cursor_y, EditKind::Insert);
+        }
+    }
+
+    /// Insert `text` verbatim at the cursor as a single edit, without the
+    /// auto-indent applied to typed newlines.
+    pub fn insert_text(&mut self, text: &str) {
+        let pos = self.cursor_char();
+        let edit = self.edit_at(pos, pos, text);
+        let end_line = self.text.char_to_line(pos) + text.matches('\n').count();
+        let end_x = match text.rfind('\n') {
+            Some(i) => text[i + 1..].chars().count(),
+            None => self.cursor_x + text.chars().count(),
+        };
+        self.apply_edit(edit, EditKind::Other, (end_x, end_line));
+        self.history.seal();
     }
 
+    /// Delete the char before the cursor. In the spaces indenting a line,
+    /// delete back to the previous indent level instead.
     pub fn delete_char(&mut self) {
         if self.cursor_x > 0 {
-            let pos = self.text.line_to_char(self.cursor_y) + self.cursor_x;
-            let line = self.text.line(self.cursor_y);
-            let nb_spaces: usize = line
-                .chars()
-                .take_while(|c| *c == ' ' && *c != '\t')
-                .map(|c| if c == ' ' { 1 } else { 0 })
-                .sum();
-
-            let chars_to_remove = if nb_spaces.is_multiple_of(4) {
-                4
-            } else if nb_spaces > 0 {
-                nb_spaces % 4
+            let pos = self.cursor_char();
+            let before = self.text.slice(pos - self.cursor_x..pos);
+            let chars_to_remove = if !self.indent.tabs && before.chars().all(|c| c == ' ') {
+                let width = self.indent.width;
+                self.cursor_x - (self.cursor_x - 1) / width * width
             } else {
                 1
             };
 
-            self.text.remove(pos - chars_to_remove..pos);
-            self.cursor_x -= chars_to_remove;
-            self.on_text_changed();
+            let edit = self.edit_at(pos - chars_to_remove, pos, "");
+            self.apply_edit(
+                edit,
+                EditKind::Delete,
+                (self.cursor_x - chars_to_remove, self.cursor_y),
+            );
         } else if self.cursor_y > 0 {
             let pos = self.text.line_to_char(self.cursor_y);
             let prev_len = self.visible_line_len(self.cursor_y - 1);
-            self.text.remove(pos - 1..pos);
-            self.cursor_y -= 1;
-            self.cursor_x = prev_len;
-            self.on_text_changed();
+            let edit = self.edit_at(pos - 1, pos, "");
+            self.apply_edit(edit, EditKind::Delete, (prev_len, self.cursor_y - 1));
         }
     }
 
+    /// Break the line at the cursor, indenting the new line from the syntax
+    /// tree. Between a pair of brackets, the closing one goes on a line of
+    /// its own below the cursor.
     pub fn newline(&mut self) {
-        let pos = self.text.line_to_char(self.cursor_y) + self.cursor_x;
-        let indent = self.indent_after(self.cursor_y);
+        let pos = self.cursor_char();
+        let blanks = self
+            .text
+            .slice(pos..)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .count();
+        let indent = self.new_line_indent(pos, pos + blanks);
+
+        let before = pos.checked_sub(1).map(|p| self.text.char(p));
+        let after = self.text.get_char(pos + blanks);
+        let indent = self.indent.whitespace(indent);
+        let (inserted, cursor_x) = if matches!(
+            (before, after),
+            (Some('{'), Some('}')) | (Some('('), Some(')')) | (Some('['), Some(']'))
+        ) {
+            let inner = self
+                .indent
+                .whitespace(self.leading_width(self.cursor_y) + self.indent.width);
+            (format!("\n{}\n{}", inner, indent), inner.chars().count())
+        } else {
+            (format!("\n{}", indent), indent.chars().count())
+        };
 
-        self.text.insert_char(pos, '\n');
-        if indent > 0 {
-            self.text.insert(pos + 1, &" ".repeat(indent));
+        let edit = self.edit_at(pos, pos + blanks, &inserted);
+        self.apply_edit(edit, EditKind::Insert, (cursor_x, self.cursor_y + 1));
+    }
+
+    pub fn cursor_char(&self) -> usize {
+        self.text.line_to_char(self.cursor_y) + self.cursor_x
+    }
+
+    /// Display column of the cursor, counting tab stops and wide chars.
+    pub fn cursor_column(&self) -> usize {
+        let start = self.text.line_to_char(self.cursor_y);
+        let before = self.text.slice(start..start + self.cursor_x).to_string();
+        column::width(&before, self.indent.tab_width)
+    }
+
+    /// Char index of the grapheme of `line` drawn over display `col`, or the
+    /// end of the line when it is shorter.
+    pub fn char_at_column(&self, line: usize, col: usize) -> usize {
+        column::to_char(&self.line_content(line), col, self.indent.tab_width)
+    }
+
+    pub fn set_cursor_char(&mut self, pos: usize) {
+        let pos = pos.min(self.text.len_chars());
+        self.cursor_y = self.text.char_to_line(pos);
+        self.cursor_x =
+            (pos - self.text.line_to_char(self.cursor_y)).min(self.visible_line_len(self.cursor_y));
+    }
+
+    /// Char range between the anchor and the cursor. Charwise selections
+    /// include the char under the cursor, linewise ones cover whole lines.
+    pub fn selection_range(&self, linewise: bool) -> Option<(usize, usize)> {
+        let (ax, ay) = self.anchor?;
+        let len = self.text.len_chars();
+
+        if linewise {
+            let top = ay.min(self.cursor_y);
+            let bottom = ay.max(self.cursor_y);
+            let end = if bottom + 1 < self.text.len_lines() {
+                self.text.line_to_char(bottom + 1)
+            } else {
+                len
+            };
+            return Some((self.text.line_to_char(top), end));
         }
 
-        self.cursor_y += 1;
-        self.cursor_x = indent;
-        self.on_text_changed();
+        let anchor = (self.text.line_to_char(ay) + ax).min(len);
+        let cursor = self.cursor_char();
+        Some((anchor.min(cursor), (anchor.max(cursor) + 1).min(len)))
+    }
+
+    /// Select the smallest syntax node around the selection, or around the
+    /// cursor when nothing is selected. The replaced selection is kept, so
+    /// `shrink_selection` can step back to it.
+    pub fn expand_selection(&mut self) -> bool {
+        let cursor = self.cursor_char();
+        let current = self.selection_range(false).unwrap_or((cursor, cursor));
+        if self.expansions.last().is_none_or(|e| e.to != current) {
+            self.expansions.clear();
+        }
+
+        let Some(tree) = self.highlighter.tree() else {
+            return false;
+        };
+        let start = self.text.char_to_byte(current.0);
+        let end = self.text.char_to_byte(current.1);
+        let Some(mut node) = tree.root_node().named_descendant_for_byte_range(start, end) else {
+            return false;
+        };
+        while node.byte_range() == (start..end) {
+            let Some(parent) = node.parent() else {
+                return false;
+            };
+            node = parent;
+        }
+
+        let to = (
+            self.text.byte_to_char(node.start_byte()),
+            self.text.byte_to_char(node.end_byte()),
+        );
+        self.expansions.push(Expansion {
+            anchor: self.anchor,
+            cursor: (self.cursor_x, self.cursor_y),
+            to,
+        });
+        self.select_chars(to.0, to.1);
+        true
+    }
+
+    /// Select the chars in `start..end`, with the cursor on the last one.
+    fn select_chars(&mut self, start: usize, end: usize) {
+        let anchor_y = self.text.char_to_line(start);
+        self.anchor = Some((start - self.text.line_to_char(anchor_y), anchor_y));
+        self.set_cursor_char(end.saturating_sub(1).max(start));
+    }
+
+    /// Select the innermost `object` (see `textobject`) around the cursor,
+    /// or only its contents with `inside`.
+    pub fn select_object(&mut self, object: &str, inside: bool) -> bool {
+        let byte = self.text.char_to_byte(self.cursor_char());
+        let Some(range) = textobject::select(&self.highlighter, &self.text, object, byte, inside)
+        else {
+            return false;
+        };
+        self.select_chars(
+            self.text.byte_to_char(range.start),
+            self.text.byte_to_char(range.end),
+        );
+        true
+    }
+
+    /// Move the cursor to the start of the next `object`, or the previous
+    /// one when `forward` is false.
+    pub fn goto_object(&mut self, object: &str, forward: bool) -> bool {
+        let byte = self.text.char_to_byte(self.cursor_char());
+        match textobject::next_start(&self.highlighter, &self.text, object, byte, forward) {
+            Some(start) => {
+                self.set_cursor_char(self.text.byte_to_char(start));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go back to the selection the last `expand_selection` replaced. Fails
+    /// once the selection has changed in between.
+    pub fn shrink_selection(&mut self) -> bool {
+        let current = self.selection_range(false);
+        match self.expansions.pop() {
+            Some(expansion) if Some(expansion.to) == current => {
+                self.anchor = expansion.anchor;
+                (self.cursor_x, self.cursor_y) = expansion.cursor;
+                true
+            }
+            _ => {
+                self.expansions.clear();
+                false
+            }
+        }
+    }
+
+    /// First and last line touched by the selection.
+    pub fn selection_lines(&self) -> Option<(usize, usize)> {
+        let (_, ay) = self.anchor?;
+        Some((ay.min(self.cursor_y), ay.max(self.cursor_y)))
+    }
+
+    pub fn selected_text(&self, linewise: bool) -> Option<String> {
+        let (start, end) = self.selection_range(linewise)?;
+        Some(self.text.slice(start..end).to_string())
+    }
+
+    /// Remove the chars in `start..end` as one undoable edit and leave the
+    /// cursor at `start`.
+    pub fn delete_range(&mut self, start: usize, end: usize) -> String {
+        let edit = self.edit_at(start, end, "");
+        let removed = edit.removed.clone();
+        let line = self.text.char_to_line(start);
+        let after = (start - self.text.line_to_char(line), line);
+        self.apply_edit(edit, EditKind::Other, after);
+        removed
+    }
+
+    pub fn delete_selection(&mut self, linewise: bool) {
+        let Some((mut start, end)) = self.selection_range(linewise) else {
+            return;
+        };
+        // Deleting the last lines also takes the newline ending the line above.
+        if linewise && end == self.text.len_chars() && start > 0 && !self.text_ends_with_newline() {
+            start -= 1;
+        }
+        self.delete_range(start, end);
+        self.anchor = None;
+        if linewise {
+            self.cursor_y = self.cursor_y.min(self.text.len_lines() - 1);
+            self.jump_to_line_indent();
+        }
+    }
+
+    /// Like `delete_selection`, but linewise selections keep one empty line
+    /// to type into.
+    pub fn change_selection(&mut self, linewise: bool) {
+        if !linewise {
+            self.delete_selection(false);
+            return;
+        }
+        let Some((top, bottom)) = self.selection_lines() else {
+            return;
+        };
+        let start = self.text.line_to_char(top);
+        let end = self.text.line_to_char(bottom) + self.visible_line_len(bottom);
+        self.delete_range(start, end);
+        self.anchor = None;
+    }
+
+    /// Replace each `(start, end, text)` char range as a single undoable
+    /// edit. Ranges must be sorted and must not overlap. The cursor ends on
+    /// the line of the last replacement.
+    pub fn replace_ranges(&mut self, ranges: &[(usize, usize, String)]) {
+        let Some((last_start, _, _)) = ranges.last() else {
+            return;
+        };
+        let shift: isize = ranges[..ranges.len() - 1]
+            .iter()
+            .map(|(start, end, text)| text.chars().count() as isize - (end - start) as isize)
+            .sum();
+
+        self.splice_ranges(ranges.iter().rev().cloned());
+        self.set_cursor_char(last_start.saturating_add_signed(shift));
+        self.jump_to_line_indent();
+        self.history
+            .set_cursor_after((self.cursor_x, self.cursor_y));
+    }
+
+    /// Indent the lines from `top` to `bottom` by one level, leaving empty
+    /// lines alone.
+    pub fn indent_lines(&mut self, top: usize, bottom: usize) {
+        let widths: Vec<(usize, usize)> = (top..=bottom)
+            .filter(|&line| self.visible_line_len(line) > 0)
+            .map(|line| (line, self.leading_width(line) + self.indent.width))
+            .collect();
+        self.set_lines_indent(&widths);
+    }
+
+    pub fn outdent_lines(&mut self, top: usize, bottom: usize) {
+        let widths: Vec<(usize, usize)> = (top..=bottom)
+            .map(|line| {
+                let width = self.leading_width(line).saturating_sub(self.indent.width);
+                (line, width)
+            })
+            .collect();
+        self.set_lines_indent(&widths);
+    }
+
+    /// Indent or outdent the cursor line by one level while typing, keeping
+    /// the cursor on the same char.
+    pub fn shift_line(&mut self, outdent: bool) {
+        let width = self.leading_width(self.cursor_y);
+        let width = if outdent {
+            width.saturating_sub(self.indent.width)
+        } else {
+            width + self.indent.width
+        };
+        self.set_line_indent(self.cursor_y, width, EditKind::Other);
+    }
+
+    /// Insert a tab, or spaces up to the next indent level.
+    pub fn insert_tab(&mut self) {
+        if self.indent.tabs {
+            self.insert_char('\t');
+            return;
+        }
+        let pos = self.cursor_char();
+        let column = self.cursor_column();
+        let spaces = " ".repeat(self.indent.width - column % self.indent.width);
+        let edit = self.edit_at(pos, pos, &spaces);
+        let cursor_after = (self.cursor_x + spaces.len(), self.cursor_y);
+        self.apply_edit(edit, EditKind::Insert, cursor_after);
+    }
+
+    /// Paste after the cursor (or before it with `before`). Linewise registers
+    /// go below (or above) the current line.
+    pub fn paste(&mut self, register: &Register, before: bool) {
+        if register.linewise {
+            let line = if before {
+                self.cursor_y
+            } else {
+                self.cursor_y + 1
+            };
+            let edit = if line < self.text.len_lines() {
+                let pos = self.text.line_to_char(line);
+                self.edit_at(pos, pos, &register.text)
+            } else {
+                // Pasting below a last line that has no newline of its own.
+                let pos = self.text.len_chars();
+                let text = format!("\n{}", register.text.trim_end_matches('\n'));
+                self.edit_at(pos, pos, &text)
+            };
+            self.apply_edit(edit, EditKind::Other, (0, line));
+            self.jump_to_line_indent();
+            return;
+        }
+
+        let line_len = self.visible_line_len(self.cursor_y);
+        let x = if before || line_len == 0 {
+            self.cursor_x
+        } else {
+            (self.cursor_x + 1).min(line_len)
+        };
+        let pos = self.text.line_to_char(self.cursor_y) + x;
+        let edit = self.edit_at(pos, pos, &register.text);
+
+        self.apply_edit(edit, EditKind::Other, (x, self.cursor_y));
+        // Leave the cursor on the last pasted char.
+        self.set_cursor_char(pos + register.text.chars().count().saturating_sub(1));
+    }
+
+    fn text_ends_with_newline(&self) -> bool {
+        let len = self.text.len_chars();
+        len > 0 && self.text.char(len - 1) == '\n'
     }
 
     pub fn jump_to_line(&mut self, line: usize) {
@@ -165,26 +702,155 @@ impl Buffer {
     }
 
     pub fn jump_to_line_indent(&mut self) {
-        self.cursor_x = self.line_indent(self.cursor_y);
+        self.cursor_x = self.leading_len(self.cursor_y);
     }
 
-    pub fn compute_scroll(&mut self, viewport_height: usize) {
+    /// Scroll so the cursor is on screen, counting a closed fold as one
+    /// line and a wrapped row as one per screen line. Without wrapping, the
+    /// view also scrolls sideways to keep the cursor's column in view. A
+    /// cursor left inside a closed fold, e.g. by a search or a jump, opens it.
+    pub fn compute_scroll(&mut self, viewport_height: usize, viewport_width: usize) {
+        self.view_width = viewport_width;
+        if self.wrap || viewport_width == 0 {
+            self.scroll_x = 0;
+        } else {
+            let column = self.cursor_column();
+            if column < self.scroll_x {
+                self.scroll_x = column;
+            } else if column >= self.scroll_x + viewport_width {
+                self.scroll_x = column + 1 - viewport_width;
+            }
+        }
+
         if viewport_height == 0 {
             return;
         }
-        if self.cursor_y < self.scroll_y {
-            self.scroll_y = self.cursor_y;
-        } else if self.cursor_y >= self.scroll_y + viewport_height {
-            self.scroll_y = self.cursor_y - viewport_height + 1;
+        self.folds.reveal(self.cursor_y);
+        self.scroll_y = self.folds.visible_row(self.scroll_y);
+        self.scroll_subline = self
+            .scroll_subline
+            .min(self.wrap_starts(self.scroll_y).len() - 1);
+        let top = (self.scroll_y, self.scroll_subline);
+        let cursor = (self.cursor_y, self.cursor_subline());
+        if cursor < top {
+            (self.scroll_y, self.scroll_subline) = cursor;
+            return;
+        }
+        let mut first = cursor;
+        for _ in 1..viewport_height {
+            match self.prev_visual(first) {
+                Some(prev) if first > top => first = prev,
+                _ => return,
+            }
         }
+        (self.scroll_y, self.scroll_subline) = top.max(first);
     }
 
-    pub fn move_word_left(&mut self) {
-        let s = self.line_content(self.cursor_y);
+    /// Scroll up while the cursor is hidden under the sticky headers pinned
+    /// over the first lines of the view.
+    pub fn scroll_below_sticky(&mut self, max: usize) {
+        let cursor = (self.cursor_y, self.cursor_subline());
+        while let Some(prev) = self.prev_visual((self.scroll_y, self.scroll_subline)) {
+            let covered = context::sticky(self, self.scroll_y, max).len();
+            if !self.display_lines(covered).contains(&cursor) {
+                return;
+            }
+            (self.scroll_y, self.scroll_subline) = prev;
+        }
+    }
+
+    /// Visual lines drawn on the first `count` screen lines from the top of
+    /// the view, as `(row, sub)` pairs where `sub` counts the screen lines of
+    /// a wrapped row.
+    pub fn display_lines(&self, count: usize) -> Vec<(usize, usize)> {
+        let nb_lines = self.text.len_lines();
+        let mut lines = Vec::new();
+        let (mut row, mut sub) = (self.scroll_y, self.scroll_subline);
+        while lines.len() < count && row < nb_lines {
+            let subs = self.wrap_starts(row).len();
+            let missing = count - lines.len();
+            lines.extend((sub..subs).map(|sub| (row, sub)).take(missing));
+            row = self.folds.next_visible(row);
+            sub = 0;
+        }
+        lines
+    }
+
+    /// Visual line of the cursor within its row, 0 unless it wraps.
+    pub fn cursor_subline(&self) -> usize {
+        self.wrap_starts(self.cursor_y)
+            .iter()
+            .rposition(|&(start, _)| start <= self.cursor_x)
+            .unwrap_or(0)
+    }
+
+    /// Display columns of `row` drawn on its visual line `sub`.
+    pub fn visual_columns(&self, row: usize, sub: usize) -> Range<usize> {
+        if self.view_width == 0 {
+            return self.scroll_x..usize::MAX;
+        }
+        if !self.wrap {
+            return self.scroll_x..self.scroll_x + self.view_width;
+        }
+        let starts = self.wrap_starts(row);
+        let start = starts.get(sub).map_or(0, |&(_, col)| col);
+        let end = starts
+            .get(sub + 1)
+            .map_or(start + self.view_width, |&(_, col)| col);
+        start..end
+    }
+
+    /// Char index of the grapheme of `row` drawn over display `col`, kept on
+    /// its visual line `sub`.
+    pub fn char_at_visual(&self, row: usize, sub: usize, col: usize) -> usize {
+        let starts = self.wrap_starts(row);
+        let start = starts.get(sub).map_or(0, |&(start, _)| start);
+        let x = self.char_at_column(row, col).max(start);
+        match starts.get(sub + 1) {
+            Some(&(next, _)) if x >= next => column::prev_grapheme(&self.line_content(row), next),
+            _ => x,
+        }
+    }
+
+    /// Char index and display column where each visual line of `row`
+    /// starts. Only wrapped rows have more than one; a closed fold stays on
+    /// one line.
+    fn wrap_starts(&self, row: usize) -> Vec<(usize, usize)> {
+        if !self.wrap || self.view_width == 0 || self.folds.hidden_end(row).is_some() {
+            return vec![(0, 0)];
+        }
+        column::wrap(
+            &self.line_content(row),
+            self.view_width,
+            self.indent.tab_width,
+        )
+    }
+
+    /// Visual line before `(row, sub)`, skipping closed folds.
+    fn prev_visual(&self, (row, sub): (usize, usize)) -> Option<(usize, usize)> {
+        if sub > 0 {
+            return Some((row, sub - 1));
+        }
+        if row == 0 {
+            return None;
+        }
+        let prev = self.folds.prev_visible(row);
+        Some((prev, self.wrap_starts(prev).len() - 1))
+    }
+
+    /// Visual line after `(row, sub)`, skipping closed folds.
+    fn next_visual(&self, (row, sub): (usize, usize)) -> Option<(usize, usize)> {
+        if sub + 1 < self.wrap_starts(row).len() {
+            return Some((row, sub + 1));
+        }
+        let next = self.folds.next_visible(row);
+        (next < self.text.len_lines()).then_some((next, 0))
+    }
 
+    pub fn move_word_left(&mut self) {
         if self.cursor_x > 0 {
-            let chars: Vec<char> = s[..self.cursor_x].chars().collect();
-            let mut i = chars.len();
+            let chars: Vec<char> = self.line_content(self.cursor_y).chars().collect();
+            let mut i = self.cursor_x.min(chars.len());
 
             while i > 0 && chars[i - 1].is_whitespace() {
                 i -= 1;
@@ -197,21 +863,19 @@ impl Buffer {
                 }
             }
 
-            self.cursor_x = chars[..i].iter().collect::<String>().len();
+            self.cursor_x = i;
         } else if self.cursor_y > 0 {
             self.cursor_y -= 1;
-            self.cursor_x = self.line_content(self.cursor_y).len();
+            self.cursor_x = self.visible_line_len(self.cursor_y);
         }
     }
 
     pub fn move_word_right(&mut self) {
-        let s = self.line_content(self.cursor_y);
-        let chars: Vec<char> = s.chars().collect();
+        let chars: Vec<char> = self.line_content(self.cursor_y).chars().collect();
         let len = chars.len();
 
-        if self.cursor_x < s.len() {
-            let ci = s[..self.cursor_x].chars().count();
-            let mut i = ci;
+        if self.cursor_x < len {
+            let mut i = self.cursor_x;
 
             let target = char_class(chars[i]);
             while i < len && char_class(chars[i]) == target {
@@ -222,7 +886,7 @@ impl Buffer {
                 i += 1;
             }
 
-            self.cursor_x = chars[..i].iter().collect::<String>().len();
+            self.cursor_x = i;
         } else if self.cursor_y < self.text.len_lines() - 1 {
             self.cursor_y += 1;
             self.cursor_x = 0;
@@ -238,58 +902,133 @@ impl Buffer {
         }
     }
 
-    /// Indent to use for a new line inserted below `ref_line`.
-    fn indent_after(&self, ref_line: usize) -> usize {
-        let s = self.text.line(ref_line).as_str().unwrap_or("");
-        let trimmed = s.trim();
-
-        if trimmed.is_empty() && ref_line > 0 {
-            return self.indent_after(ref_line - 1);
+    /// Reindent the non-blank lines from `top` to `bottom` from the syntax
+    /// tree as a single undo step, and return how many lines changed.
+    pub fn reindent(&mut self, top: usize, bottom: usize) -> usize {
+        let mut widths: Vec<(usize, usize)> = Vec::new();
+        for line in top..=bottom {
+            if self.line_content(line).trim().is_empty() {
+                continue;
+            }
+            let width_of = |row: usize| match widths.binary_search_by_key(&row, |&(l, _)| l) {
+                Ok(i) => widths[i].1,
+                Err(_) => self.leading_width(row),
+            };
+            let width = self
+                .rule_width(self.line_indent_rule(line), width_of)
+                .unwrap_or_else(|| self.leading_width(line));
+            widths.push((line, width));
         }
 
-        let leading = s.len() - trimmed.len() - 1;
+        self.cursor_y = top;
+        self.set_lines_indent(&widths)
+    }
 
-        if trimmed.ends_with('{') {
-            return leading + 4;
+    /// Give each of the `(line, width)` pairs, sorted by line, an indent of
+    /// `width` columns as a single undo step with one reparse, and move the
+    /// cursor to the indent of its line. Returns how many lines changed.
+    fn set_lines_indent(&mut self, widths: &[(usize, usize)]) -> usize {
+        let edits: Vec<(usize, usize, String)> = widths
+            .iter()
+            .rev()
+            .filter_map(|&(line, width)| {
+                let start = self.text.line_to_char(line);
+                let end = start + self.leading_len(line);
+                let whitespace = self.indent.whitespace(width);
+                (self.text.slice(start..end) != whitespace.as_str())
+                    .then_some((start, end, whitespace))
+            })
+            .collect();
+        let changed = edits.len();
+        self.splice_ranges(edits);
+        self.jump_to_line_indent();
+        if changed > 0 {
+            self.history
+                .set_cursor_after((self.cursor_x, self.cursor_y));
         }
+        changed
+    }
 
-        if trimmed.starts_with('.') && trimmed.ends_with(';') {
-            return self.skip_chain(ref_line);
+    /// Reindent `line` from the syntax tree.
+    fn reindent_line(&mut self, line: usize, kind: EditKind) {
+        let rule = self.line_indent_rule(line);
+        if let Some(width) = self.rule_width(rule, |row| self.leading_width(row)) {
+            self.set_line_indent(line, width, kind);
         }
-
-        leading
     }
 
-    /// Where to place the cursor on the current line.
-    /// Non-empty -> start of content. Empty -> derive from above.
-    fn line_indent(&self, line: usize) -> usize {
-        let s = self.text.line(line).as_str().unwrap_or("");
-        let trimmed = s.trim_start();
-
-        if !trimmed.is_empty() {
-            return s.len() - trimmed.len();
+    /// Replace the leading whitespace of `line` with an indent of `width`
+    /// columns, keeping the cursor on the same char. Returns whether the line
+    /// changed.
+    fn set_line_indent(&mut self, line: usize, width: usize, kind: EditKind) -> bool {
+        let current = self.leading_len(line);
+        let start = self.text.line_to_char(line);
+        let whitespace = self.indent.whitespace(width);
+        if self.text.slice(start..start + current) == whitespace.as_str() {
+            return false;
         }
+        let len = whitespace.chars().count();
+        let cursor_x = match self.cursor_x.checked_sub(current) {
+            _ if self.cursor_y != line => self.cursor_x,
+            Some(after) => len + after,
+            None => self.cursor_x.min(len),
+        };
+        let edit = self.edit_at(start, start + current, &whitespace);
+        self.apply_edit(edit, kind, (cursor_x, self.cursor_y));
+        true
+    }
 
-        if line > 0 {
-            return self.indent_after(line - 1);
-        }
+    /// Indent rule for the existing `line`.
+    fn line_indent_rule(&self, line: usize) -> Option<Indent> {
+        let start = self.text.line_to_char(line);
+        let first = start + self.leading_len(line);
+        indent::compute(
+            &self.highlighter,
+            &self.text,
+            self.text.char_to_byte(start),
+            self.text.char_to_byte(first),
+        )
+    }
 
-        0
+    /// Indent width of a line about to be broken off at char `pos`, whose
+    /// text will start at char `first`. Without a rule for it, the new line
+    /// keeps the indent of the current one.
+    fn new_line_indent(&self, pos: usize, first: usize) -> usize {
+        let rule = indent::compute(
+            &self.highlighter,
+            &self.text,
+            self.text.char_to_byte(pos),
+            self.text.char_to_byte(first),
+        );
+        self.rule_width(rule, |row| self.leading_width(row))
+            .unwrap_or_else(|| self.leading_width(self.text.char_to_line(pos)))
     }
 
-    fn skip_chain(&self, line: usize) -> usize {
-        if line == 0 {
-            return 0;
-        }
-        let above = line - 1;
-        let s = self.text.line(above).as_str().unwrap_or("");
-        let trimmed = s.trim_start();
+    /// Columns taken by the indent of `line`.
+    fn leading_width(&self, line: usize) -> usize {
+        let start = self.text.line_to_char(line);
+        let indent = self.text.slice(start..start + self.leading_len(line));
+        column::width(&indent.to_string(), self.indent.tab_width)
+    }
 
-        if trimmed.starts_with('.') {
-            return self.skip_chain(above);
-        }
+    /// Number of whitespace chars at the start of `line`.
+    fn leading_len(&self, line: usize) -> usize {
+        self.text
+            .line(line)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .count()
+    }
 
-        s.len() - trimmed.len()
+    /// Indent width `rule` gives a line, from the widths of earlier lines.
+    /// None when the line should keep its own.
+    fn rule_width(&self, rule: Option<Indent>, width_of: impl Fn(usize) -> usize) -> Option<usize> {
+        match rule? {
+            Indent::Below(row) => Some(width_of(row) + self.indent.width),
+            Indent::Like(row) => Some(width_of(row)),
+            Indent::Top => Some(0),
+            Indent::Keep => None,
+        }
     }
 
     fn line_content(&self, y: usize) -> String {
@@ -0,0 +1,117 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Cells taken by `grapheme` when drawn at display `column`: up to the next
+/// tab stop for a tab, two for East Asian wide chars, and none for other
+/// control chars, which aren't drawn.
+pub fn grapheme_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        let tab_width = tab_width.max(1);
+        tab_width - column % tab_width
+    } else if grapheme.contains(char::is_control) {
+        0
+    } else {
+        grapheme.width()
+    }
+}
+
+/// Display width of `text` drawn from the start of a line.
+pub fn width(text: &str, tab_width: usize) -> usize {
+    text.graphemes(true).fold(0, |column, grapheme| {
+        column + grapheme_width(grapheme, column, tab_width)
+    })
+}
+
+/// Char index of the grapheme of `line` drawn over display `column`, or the
+/// line's length when the column is past its end.
+pub fn to_char(line: &str, column: usize, tab_width: usize) -> usize {
+    let (mut index, mut start) = (0, 0);
+    for grapheme in line.graphemes(true) {
+        let end = start + grapheme_width(grapheme, start, tab_width);
+        if end > column {
+            return index;
+        }
+        index += grapheme.chars().count();
+        start = end;
+    }
+    index
+}
+
+/// Char index of the grapheme boundary after char `index` of `line`.
+pub fn next_grapheme(line: &str, index: usize) -> usize {
+    let mut start = 0;
+    for grapheme in line.graphemes(true) {
+        start += grapheme.chars().count();
+        if start > index {
+            return start;
+        }
+    }
+    start
+}
+
+/// Char index of the grapheme boundary before char `index` of `line`.
+pub fn prev_grapheme(line: &str, index: usize) -> usize {
+    let mut start = 0;
+    for grapheme in line.graphemes(true) {
+        let end = start + grapheme.chars().count();
+        if end >= index {
+            return start;
+        }
+        start = end;
+    }
+    start
+}
+
+/// Char index and display column where each visual line of `line` starts
+/// when it is wrapped at `width` columns. A line filling its last visual
+/// line to the edge gets an empty one after it, for the cursor to go to.
+pub fn wrap(line: &str, width: usize, tab_width: usize) -> Vec<(usize, usize)> {
+    let mut starts = vec![(0, 0)];
+    let (mut index, mut col, mut line_start) = (0, 0, 0);
+    for grapheme in line.graphemes(true) {
+        let end = col + grapheme_width(grapheme, col, tab_width);
+        if end > line_start + width && col > line_start {
+            starts.push((index, col));
+            line_start = col;
+        }
+        index += grapheme.chars().count();
+        col = end;
+    }
+    if col >= line_start + width {
+        starts.push((index, col));
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_ascii() {
+        assert_eq!(wrap("abcdefg", 3, 4), vec![(0, 0), (3, 3), (6, 6)]);
+        assert_eq!(wrap("ab", 3, 4), vec![(0, 0)]);
+        assert_eq!(wrap("", 3, 4), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn wrap_full_last_line_gets_an_empty_one() {
+        assert_eq!(wrap("abcdef", 3, 4), vec![(0, 0), (3, 3), (6, 6)]);
+    }
+
+    #[test]
+    fn wrap_wide_chars() {
+        // A wide char that doesn't fit moves whole to the next line.
+        assert_eq!(wrap("ab日x", 3, 4), vec![(0, 0), (2, 2), (4, 5)]);
+        assert_eq!(wrap("日本語", 4, 4), vec![(0, 0), (2, 4)]);
+        // Wider than the view: it still gets a line of its own.
+        assert_eq!(wrap("日日", 1, 4), vec![(0, 0), (1, 2), (2, 4)]);
+    }
+
+    #[test]
+    fn wrap_tabs_and_graphemes() {
+        assert_eq!(wrap("\tab", 4, 4), vec![(0, 0), (1, 4)]);
+        // `e` plus a combining accent is one grapheme of two chars.
+        assert_eq!(wrap("ae\u{301}bc", 2, 4), vec![(0, 0), (3, 2), (5, 4)]);
+    }
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+/// Directory holding user configuration: `$XDG_CONFIG_HOME/text-editor`,
+/// falling back to `~/.config/text-editor`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("text-editor"))
+}
+
+/// Directory for state kept between sessions, such as undo history:
+/// `$XDG_STATE_HOME/text-editor`, falling back to `~/.local/state/text-editor`.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+    Some(base.join("text-editor"))
+}
+
+/// Read `relative` from the config directory, if the user provided it.
+pub fn read_user_file(relative: &str) -> Option<String> {
+    std::fs::read_to_string(config_dir()?.join(relative)).ok()
+}
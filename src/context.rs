@@ -0,0 +1,124 @@
+use ropey::Rope;
+use std::{cmp::Reverse, ops::Range};
+use tree_sitter::{self as ts, StreamingIterator};
+
+use crate::{buffer::Buffer, highlighter::Highlighter, outline};
+
+/// Most headers pinned at the top of the text at once.
+pub const MAX_STICKY_LINES: usize = 3;
+
+/// Labels longer than this many chars are cut short.
+const MAX_LABEL_CHARS: usize = 40;
+
+/// A construct enclosing a position: an outline symbol such as
+/// `fn handle_command`, or one picked out by the language's `context` query
+/// such as a `match`.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub label: String,
+    /// Line the construct starts on.
+    pub line: usize,
+    /// Byte range of the construct.
+    pub range: Range<usize>,
+}
+
+/// Constructs containing `byte`, outermost first.
+pub fn at(highlighter: &Highlighter, text: &Rope, byte: usize) -> Vec<Context> {
+    let within = byte..byte + 1;
+    let mut found: Vec<Context> = outline::symbols(highlighter, text, Some(within.clone()))
+        .into_iter()
+        .filter(|symbol| symbol.range.contains(&byte))
+        .map(|symbol| Context {
+            label: shorten(&format!("{} {}", symbol.keyword(), symbol.name)),
+            line: text.byte_to_line(symbol.range.start),
+            range: symbol.range,
+        })
+        .collect();
+    found.extend(query_contexts(highlighter, text, within));
+
+    found.sort_by_key(|c| (c.range.start, Reverse(c.range.end)));
+    found.dedup_by(|a, b| a.range == b.range);
+    found
+}
+
+/// Headers to pin above the text when the view starts at `top_line`: the
+/// first line of each construct around it that begins above it, at most
+/// `max` of them and one per line, keeping the innermost.
+pub fn sticky(buf: &Buffer, top_line: usize, max: usize) -> Vec<Context> {
+    if top_line == 0 || top_line >= buf.text.len_lines() {
+        return Vec::new();
+    }
+    let byte = buf.text.line_to_byte(top_line);
+    let mut headers: Vec<Context> = at(&buf.highlighter, &buf.text, byte)
+        .into_iter()
+        .filter(|c| c.line < top_line)
+        .collect();
+    headers.dedup_by_key(|c| c.line);
+    let skip = headers.len().saturating_sub(max);
+    headers.drain(..skip);
+    headers
+}
+
+/// Matches of the `context` query containing `within`.
+fn query_contexts(highlighter: &Highlighter, text: &Rope, within: Range<usize>) -> Vec<Context> {
+    let (Some(tree), Some(query)) = (
+        highlighter.tree(),
+        highlighter
+            .language()
+            .and_then(|lang| lang.context.as_ref()),
+    ) else {
+        return Vec::new();
+    };
+    let context_index = query.capture_index_for_name("context");
+    let label_index = query.capture_index_for_name("label");
+
+    let mut found = Vec::new();
+    let mut cursor = ts::QueryCursor::new();
+    cursor.set_byte_range(within.clone());
+    let mut matches = cursor.matches(query, tree.root_node(), |node: ts::Node| {
+        text.byte_slice(node.byte_range())
+            .chunks()
+            .map(str::as_bytes)
+    });
+    while let Some(m) = matches.next() {
+        let node_for = |index| {
+            m.captures
+                .iter()
+                .find(|c| Some(c.index) == index)
+                .map(|c| c.node)
+        };
+        let Some(node) = node_for(context_index) else {
+            continue;
+        };
+        if !node.byte_range().contains(&within.start) {
+            continue;
+        }
+        let property = query
+            .property_settings(m.pattern_index)
+            .iter()
+            .find(|p| &*p.key == "label")
+            .and_then(|p| p.value.as_deref().map(str::to_string));
+        let captured =
+            node_for(label_index).map(|label| text.byte_slice(label.byte_range()).to_string());
+        let label = property
+            .or(captured)
+            .unwrap_or_else(|| node.kind().to_string());
+        found.push(Context {
+            label: shorten(&label),
+            line: node.start_position().row,
+            range: node.byte_range(),
+        });
+    }
+    found
+}
+
+/// `label` on one line and cut to `MAX_LABEL_CHARS`.
+fn shorten(label: &str) -> String {
+    let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+    if label.chars().count() <= MAX_LABEL_CHARS {
+        return label;
+    }
+    let mut short: String = label.chars().take(MAX_LABEL_CHARS - 1).collect();
+    short.push('…');
+    short
+}
@@ -24,6 +24,9 @@ pub struct Diagnostic {
 pub struct DiagnosticState {
     pub diagnostics: Vec<Diagnostic>,
     pub is_running: bool,
+    /// File to check again once the running check finishes, because
+    /// something changed while it ran.
+    rerun: Option<PathBuf>,
 }
 
 impl DiagnosticState {
@@ -31,6 +34,7 @@ impl DiagnosticState {
         Self {
             diagnostics: Vec::new(),
             is_running: false,
+            rerun: None,
         }
     }
 
@@ -49,7 +53,7 @@ impl DiagnosticState {
     }
 }
 
-fn find_project_dir(file: &Path) -> Option<PathBuf> {
+pub fn find_project_dir(file: &Path) -> Option<PathBuf> {
     let mut dir = file.parent().map(|p| p.to_path_buf());
     loop {
         match &dir {
@@ -144,12 +148,25 @@ fn parse_diagnostics(
     diags
 }
 
-fn run_cargo_check(state: Arc<Mutex<DiagnosticState>>, file: PathBuf) {
-    if let Ok(mut s) = state.lock() {
-        s.is_running = true;
+fn run_cargo_check(state: Arc<Mutex<DiagnosticState>>, mut file: PathBuf) {
+    loop {
+        let diags = check_file(&file);
+        let Ok(mut s) = state.lock() else {
+            return;
+        };
+        s.diagnostics = diags;
+        match s.rerun.take() {
+            Some(next) => file = next,
+            None => {
+                s.is_running = false;
+                return;
+            }
+        }
     }
+}
 
-    let project_dir = find_project_dir(&file);
+fn check_file(file: &Path) -> Vec<Diagnostic> {
+    let project_dir = find_project_dir(file);
 
     let mut cmd = Command::new("cargo");
     cmd.args(["clippy", "--message-format=json", "--color=never"]);
@@ -157,10 +174,10 @@ fn run_cargo_check(state: Arc<Mutex<DiagnosticState>>, file: PathBuf) {
         cmd.current_dir(dir);
     }
 
-    let diags = match cmd.output() {
+    match cmd.output() {
         Ok(out) => {
             let stdout = String::from_utf8_lossy(&out.stdout);
-            parse_diagnostics(&stdout, &file, project_dir.as_deref())
+            parse_diagnostics(&stdout, file, project_dir.as_deref())
         }
         Err(e) => vec![Diagnostic {
             level: DiagnosticLevel::Error,
@@ -168,20 +185,21 @@ fn run_cargo_check(state: Arc<Mutex<DiagnosticState>>, file: PathBuf) {
             line: None,
             column: None,
         }],
-    };
-
-    if let Ok(mut s) = state.lock() {
-        s.diagnostics = diags;
-        s.is_running = false;
     }
 }
 
+/// Check `file` in the background. While a check is already running, the
+/// request is queued instead and runs as soon as that one finishes.
 pub fn spawn_cargo_check(state: &Arc<Mutex<DiagnosticState>>, file: &Path) {
-    if let Ok(s) = state.lock()
-        && s.is_running
-    {
+    let Ok(mut s) = state.lock() else {
+        return;
+    };
+    if s.is_running {
+        s.rerun = Some(file.to_path_buf());
         return;
     }
+    s.is_running = true;
+    drop(s);
     let state = Arc::clone(state);
     let file = file.to_path_buf();
     thread::spawn(move || run_cargo_check(state, file));
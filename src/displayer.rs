@@ -1,18 +1,23 @@
-use std::io::Stdout;
+use std::{io::Stdout, ops::Range};
 
 use ratatui::{
     Frame, Terminal,
     layout::{Constraint, Direction, Layout, Position, Rect},
     prelude::CrosstermBackend,
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
+    buffer::Buffer,
+    column,
+    context::{self, MAX_STICKY_LINES},
     diagnostic::{DiagnosticLevel, DiagnosticState},
-    editor::Editor,
+    editor::{Editor, SidePanel},
     mode::EditorMode,
+    theme::UiTheme,
 };
 
 const GUTTER_WIDTH: u16 = 7;
@@ -31,7 +36,11 @@ impl Displayer {
     }
 
     pub fn draw(&mut self, editor: &mut Editor) -> anyhow::Result<()> {
-        let is_cursor_visible = editor.mode == EditorMode::Nav || editor.mode == EditorMode::Insert;
+        let is_cursor_visible = editor.mode == EditorMode::Nav
+            || editor.mode == EditorMode::Insert
+            || editor.mode == EditorMode::Search
+            || editor.mode == EditorMode::Confirm
+            || editor.mode.is_visual();
         if is_cursor_visible {
             self.terminal.show_cursor()?;
         } else {
@@ -42,6 +51,7 @@ impl Displayer {
 
         self.terminal.draw(|f| {
             let size = f.area();
+            f.render_widget(Block::default().style(editor.theme.ui.background), size);
 
             // top
             // editor
@@ -55,7 +65,7 @@ impl Displayer {
                 ])
                 .split(size);
 
-            editor.editor_max_height = vertical[1].height - 1;
+            editor.editor_max_height = vertical[1].height.saturating_sub(1);
 
             Self::render_tab_bar(editor, f, vertical[0]);
             Self::render_status(editor, &diag, f, vertical[2]);
@@ -79,16 +89,16 @@ impl Displayer {
             };
 
             if editor.show_tree {
-                editor.file_tree.render(f, main_h[0]);
+                editor.file_tree.render(f, main_h[0], &editor.theme.ui);
             }
 
             let editor_area = if editor.show_tree {
-                editor.editor_start_x = main_h[0].width + GUTTER_WIDTH;
                 main_h[1]
             } else {
-                editor.editor_start_x = 1;
                 main_h[0]
             };
+            editor.editor_start_x = editor_area.x + 1 + GUTTER_WIDTH;
+            editor.editor_text_width = editor_area.width.saturating_sub(2 + GUTTER_WIDTH);
             let side_panel = if editor.show_tree {
                 main_h[2]
             } else {
@@ -96,7 +106,30 @@ impl Displayer {
             };
 
             Self::render_editor(editor, &diag, f, editor_area, is_cursor_visible);
-            Self::render_diagnostics(&diag, f, side_panel);
+            match editor.side_panel {
+                SidePanel::Diagnostics => {
+                    Self::render_diagnostics(&diag, f, side_panel, &editor.theme.ui)
+                }
+                SidePanel::Search => editor.project_search.render(
+                    f,
+                    side_panel,
+                    editor.mode == EditorMode::ProjectSearch,
+                    &editor.theme.ui,
+                ),
+                SidePanel::Outline => editor.outline.render(
+                    f,
+                    side_panel,
+                    editor.mode == EditorMode::Outline,
+                    editor
+                        .buf()
+                        .map(|buf| buf.text.char_to_byte(buf.cursor_char())),
+                    &editor.theme.ui,
+                ),
+            }
+
+            if let Some(finder) = &editor.finder {
+                finder.render(f, vertical[1], &editor.theme.ui);
+            }
         })?;
 
         Ok(())
@@ -116,39 +149,96 @@ impl Displayer {
     ) {
         let visible_height = area.height.saturating_sub(2) as usize;
         let Some(buf) = editor.buf() else { return };
+        let selection = if editor.mode.is_visual() {
+            buf.selection_range(editor.mode == EditorMode::VisualLine)
+        } else {
+            None
+        };
 
-        let lines: Vec<Line> = (buf.scroll_y
-            ..buf.text.len_lines().min(buf.scroll_y + visible_height))
-            .map(|i| {
-                let has_err = diag
-                    .diagnostics
-                    .iter()
-                    .any(|d| d.line == Some(i) && d.level == DiagnosticLevel::Error);
-                let has_warn = diag
-                    .diagnostics
-                    .iter()
-                    .any(|d| d.line == Some(i) && d.level == DiagnosticLevel::Warning);
-                let num_color = if has_err {
-                    Color::Red
-                } else if has_warn {
-                    Color::Yellow
-                } else {
-                    Color::DarkGray
-                };
+        let ui = &editor.theme.ui;
+        let mut lines = Vec::new();
+        if editor.show_breadcrumb {
+            let byte = buf.text.char_to_byte(buf.cursor_char());
+            let crumbs: Vec<String> = context::at(&buf.highlighter, &buf.text, byte)
+                .into_iter()
+                .map(|c| c.label)
+                .collect();
+            lines.push(Line::from(Span::styled(crumbs.join(" › "), ui.breadcrumb)));
+        }
+        let text_top = lines.len();
+
+        let screen_lines = buf.display_lines(visible_height.saturating_sub(text_top));
+        let mut row: Option<(usize, Vec<Span<'static>>)> = None;
+        for &(i, sub) in &screen_lines {
+            let has_err = diag
+                .diagnostics
+                .iter()
+                .any(|d| d.line == Some(i) && d.level == DiagnosticLevel::Error);
+            let has_warn = diag
+                .diagnostics
+                .iter()
+                .any(|d| d.line == Some(i) && d.level == DiagnosticLevel::Warning);
+            let num_style = if has_err {
+                ui.gutter_error
+            } else if has_warn {
+                ui.gutter_warning
+            } else {
+                ui.gutter
+            };
+
+            let fold_marker = match buf.folds.region_at(i) {
+                Some(_) if buf.folds.is_closed(i) => '▸',
+                Some(_) => '▾',
+                None => ' ',
+            };
+            let num = if sub > 0 {
+                Span::styled(format!("{:>4} │ ", "↪"), num_style)
+            } else {
+                Span::styled(format!("{:>4}{}│ ", i, fold_marker), num_style)
+            };
 
-                let num = Span::styled(format!("{:>4} │ ", i), Style::default().fg(num_color));
+            if row.as_ref().is_none_or(|(r, _)| *r != i) {
+                row = Some((i, Self::styled_row(editor, buf, i, selection)));
+            }
+            let Some((_, spans)) = &row else { continue };
+            let mut line = vec![num];
+            line.extend(clip_columns(
+                spans,
+                buf.visual_columns(i, sub),
+                buf.indent.tab_width,
+            ));
+            lines.push(Line::from(line));
+        }
 
-                let mut text = buf.text.line(i).to_string();
+        if editor.sticky_headers {
+            let width = area.width.saturating_sub(2 + GUTTER_WIDTH) as usize;
+            let headers = context::sticky(buf, buf.scroll_y, MAX_STICKY_LINES);
+            for (slot, header) in lines[text_top..].iter_mut().zip(&headers) {
+                let mut text = buf.text.line(header.line).to_string();
                 if text.ends_with('\n') {
                     text.pop();
                 }
-
-                let mut spans = vec![num];
-                spans.extend(buf.highlighter.highlight_line(i, &text));
-
-                Line::from(spans)
-            })
-            .collect();
+                let mut line = vec![Span::styled(
+                    format!("{:>4} │ ", header.line),
+                    ui.gutter.patch(ui.sticky),
+                )];
+                let spans = buf.highlighter.highlight_line(header.line, &text);
+                let columns = buf.visual_columns(header.line, 0);
+                let len = column::width(&text, buf.indent.tab_width)
+                    .min(columns.end)
+                    .saturating_sub(columns.start);
+                line.extend(
+                    clip_columns(&spans, columns, buf.indent.tab_width)
+                        .into_iter()
+                        .map(|span| span.patch_style(ui.sticky)),
+                );
+                line.push(Span::styled(
+                    " ".repeat(width.saturating_sub(len)),
+                    ui.sticky,
+                ));
+                *slot = Line::from(line);
+            }
+        }
 
         f.render_widget(
             Paragraph::new(lines).block(
@@ -160,23 +250,87 @@ impl Displayer {
         );
 
         if show_cursor {
-            let cursor_x = buf.cursor_x as u16 + GUTTER_WIDTH + area.x + 1;
-            let cursor_y = (buf.cursor_y.saturating_sub(buf.scroll_y)) as u16 + area.y + 1;
+            let subline = buf.cursor_subline();
+            let columns = buf.visual_columns(buf.cursor_y, subline);
+            let column = buf.cursor_column().saturating_sub(columns.start);
+            let cursor_x = column as u16 + GUTTER_WIDTH + area.x + 1;
+            let screen_line = screen_lines
+                .iter()
+                .position(|&line| line == (buf.cursor_y, subline))
+                .unwrap_or(0);
+            let cursor_y = (text_top + screen_line) as u16 + area.y + 1;
             f.set_cursor_position(Position::new(cursor_x, cursor_y));
         }
     }
 
+    /// Styled text of row `i`, with search matches, the selection and a
+    /// closed fold's summary patched over the highlighting.
+    fn styled_row(
+        editor: &Editor,
+        buf: &Buffer,
+        i: usize,
+        selection: Option<(usize, usize)>,
+    ) -> Vec<Span<'static>> {
+        let ui = &editor.theme.ui;
+        let mut text = buf.text.line(i).to_string();
+        if text.ends_with('\n') {
+            text.pop();
+        }
+
+        let mut spans = buf.highlighter.highlight_line(i, &text);
+        let line_start = buf.text.line_to_char(i);
+        let line_len = text.chars().count();
+
+        let cursor = buf.cursor_char();
+        for &(start, end) in editor.search.matches_in(line_start, line_start + line_len) {
+            let patch = if start == cursor {
+                ui.search_current
+            } else {
+                ui.search_match
+            };
+            spans = overlay(
+                spans,
+                start.max(line_start) - line_start,
+                end.min(line_start + line_len) - line_start,
+                patch,
+            );
+        }
+
+        if let Some(r) = editor.confirm.as_ref().and_then(|c| c.current()) {
+            let from = r.start.max(line_start) - line_start;
+            let to = r.end.min(line_start + line_len).saturating_sub(line_start);
+            if r.start < line_start + line_len && r.end > line_start && from < to {
+                spans = overlay(spans, from, to, ui.search_current);
+            }
+        }
+
+        if let Some((start, end)) = selection {
+            let from = start.max(line_start) - line_start;
+            let to = end.saturating_sub(line_start).min(line_len + 1);
+            if from < to {
+                let patch = ui.selection;
+                spans = overlay(spans, from, to.min(line_len), patch);
+                // Show the selected line break as a trailing cell.
+                if to > line_len {
+                    spans.push(Span::styled(" ", patch));
+                }
+            }
+        }
+
+        if let Some(last) = buf.folds.hidden_end(i) {
+            spans.push(Span::styled(format!(" ⋯ {} lines ", last - i), ui.fold));
+        }
+        spans
+    }
+
     fn render_tab_bar(editor: &Editor, f: &mut Frame, rect: Rect) {
         let mut spans: Vec<Span> = Vec::new();
         for (i, buf) in editor.buffers.iter().enumerate() {
             let is_active = editor.active_buffer.map(|ab| ab == i).unwrap_or(false);
             let style = if is_active {
-                Style::default()
-                    .fg(Color::Black)
-                    .bg(Color::White)
-                    .add_modifier(Modifier::BOLD)
+                editor.theme.ui.tab_active
             } else {
-                Style::default().fg(Color::DarkGray)
+                editor.theme.ui.tab_inactive
             };
             spans.push(Span::styled(format!(" {} ", buf.display_name()), style));
             spans.push(Span::raw("│"));
@@ -185,31 +339,51 @@ impl Displayer {
     }
 
     fn render_status(editor: &Editor, diag: &DiagnosticState, f: &mut Frame, rect: Rect) {
+        let ui = &editor.theme.ui;
         let mut components = if let Some(buf) = editor.buf()
             && let Some(active_buffer) = editor.active_buffer
         {
             let diag_info = if diag.is_running {
-                Span::styled(" [checking...] ", Style::default().fg(Color::Gray))
+                Span::styled(" [checking...] ", ui.status_checking)
             } else {
                 let e = diag.error_count();
                 let w = diag.warning_count();
                 if e > 0 || w > 0 {
                     Span::styled(
                         format!(" [E:{} W:{}] ", e, w),
-                        Style::default().fg(if e > 0 { Color::Red } else { Color::Yellow }),
+                        if e > 0 {
+                            ui.status_error
+                        } else {
+                            ui.status_warning
+                        },
                     )
                 } else {
-                    Span::styled(" [✓] ", Style::default().fg(Color::Green))
+                    Span::styled(" [✓] ", ui.status_ok)
                 }
             };
 
             vec![
                 Span::styled(format!(" {} ", editor.mode), editor.mode.get_style()),
+                Span::styled(format!(" {} ", buf.display_name()), ui.status_file),
+                Span::raw(format!(
+                    "  {}:{} ",
+                    buf.cursor_y + 1,
+                    buf.cursor_column() + 1
+                )),
                 Span::styled(
-                    format!(" {} ", buf.display_name()),
-                    Style::default().fg(Color::Black).bg(Color::White),
+                    format!(" {} ", buf.highlighter.language_name()),
+                    ui.status_info,
                 ),
-                Span::raw(format!("  {}:{} ", buf.cursor_y + 1, buf.cursor_x + 1)),
+                Span::styled(format!(" {} ", buf.indent), ui.status_info),
+                Span::raw(if editor.search.matches.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        " match {}/{} ",
+                        editor.search.index_at(buf.cursor_char()),
+                        editor.search.matches.len()
+                    )
+                }),
                 Span::raw(format!(
                     " [{}/{}] ",
                     active_buffer + 1,
@@ -226,12 +400,25 @@ impl Displayer {
 
         if let EditorMode::Command { command_str, .. } = &editor.mode {
             components.push(Span::raw(format!(" :{} ", command_str)));
+        } else if let Some(r) = editor.confirm.as_ref().and_then(|c| c.current()) {
+            components.push(Span::styled(
+                format!(" replace with {:?} (y/n/a/q/l)? ", r.text),
+                ui.status_warning,
+            ));
+        } else if editor.mode == EditorMode::Search {
+            let prompt = if editor.search.forward { '/' } else { '?' };
+            components.push(Span::raw(format!(" {}{} ", prompt, editor.search.query)));
+        } else if let Some(message) = &editor.message {
+            components.push(Span::styled(format!(" {} ", message), ui.status_info));
         }
 
-        f.render_widget(Paragraph::new(Line::from(components)), rect);
+        f.render_widget(
+            Paragraph::new(Line::from(components)).style(ui.status),
+            rect,
+        );
     }
 
-    fn render_diagnostics(diag: &DiagnosticState, f: &mut Frame, area: Rect) {
+    fn render_diagnostics(diag: &DiagnosticState, f: &mut Frame, area: Rect, ui: &UiTheme) {
         let title = if diag.is_running {
             " Diagnostics (checking...) "
         } else if diag.diagnostics.is_empty() {
@@ -245,12 +432,12 @@ impl Displayer {
         if diag.is_running {
             lines.push(Line::from(Span::styled(
                 "⟳ Running cargo check...",
-                Style::default().fg(Color::Gray),
+                ui.status_checking,
             )));
         } else if diag.diagnostics.is_empty() {
             lines.push(Line::from(Span::styled(
                 "✓ No errors or warnings",
-                Style::default().fg(Color::Green),
+                ui.status_ok,
             )));
         } else {
             let e = diag.error_count();
@@ -259,15 +446,13 @@ impl Displayer {
             if e > 0 {
                 summary.push(Span::styled(
                     format!(" {} error{} ", e, if e > 1 { "s" } else { "" }),
-                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                    ui.status_error.add_modifier(Modifier::BOLD),
                 ));
             }
             if w > 0 {
                 summary.push(Span::styled(
                     format!(" {} warning{} ", w, if w > 1 { "s" } else { "" }),
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
+                    ui.status_warning.add_modifier(Modifier::BOLD),
                 ));
             }
             lines.push(Line::from(summary));
@@ -276,9 +461,9 @@ impl Displayer {
             ));
 
             for d in &diag.diagnostics {
-                let (icon, color) = match d.level {
-                    DiagnosticLevel::Error => ("✗", Color::Red),
-                    DiagnosticLevel::Warning => ("▲", Color::Yellow),
+                let (icon, style) = match d.level {
+                    DiagnosticLevel::Error => ("✗", ui.status_error),
+                    DiagnosticLevel::Warning => ("▲", ui.status_warning),
                 };
                 let loc = match (d.line, d.column) {
                     (Some(l), Some(c)) => format!(" L{}:{}", l, c),
@@ -286,8 +471,8 @@ impl Displayer {
                     _ => String::new(),
                 };
                 lines.push(Line::from(vec![
-                    Span::styled(format!("{} ", icon), Style::default().fg(color)),
-                    Span::styled(loc, Style::default().fg(Color::DarkGray)),
+                    Span::styled(format!("{} ", icon), style),
+                    Span::styled(loc, ui.diag_location),
                 ]));
 
                 let max_w = area.width.saturating_sub(4) as usize;
@@ -299,10 +484,7 @@ impl Displayer {
                         .chunks(max_w)
                         .map(|c| c.iter().collect::<String>())
                     {
-                        lines.push(Line::from(Span::styled(
-                            format!("  {}", chunk),
-                            Style::default().fg(color),
-                        )));
+                        lines.push(Line::from(Span::styled(format!("  {}", chunk), style)));
                     }
                 }
                 lines.push(Line::from(""));
@@ -312,7 +494,7 @@ impl Displayer {
         let block = Block::default()
             .title(title)
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::DarkGray));
+            .border_style(ui.border);
 
         f.render_widget(
             Paragraph::new(lines)
@@ -322,3 +504,73 @@ impl Displayer {
         );
     }
 }
+
+/// Patch `patch` over the chars `start..end` of a styled line, splitting the
+/// spans at the boundaries.
+fn overlay(
+    spans: Vec<Span<'static>>,
+    start: usize,
+    end: usize,
+    patch: Style,
+) -> Vec<Span<'static>> {
+    let mut result = Vec::with_capacity(spans.len() + 2);
+    let mut pos = 0;
+
+    for span in spans {
+        let len = span.content.chars().count();
+        let (s, e) = (start.clamp(pos, pos + len), end.clamp(pos, pos + len));
+        if s == e {
+            result.push(span);
+        } else {
+            let chars: Vec<char> = span.content.chars().collect();
+            let parts = [
+                (0, s - pos, span.style),
+                (s - pos, e - pos, span.style.patch(patch)),
+                (e - pos, len, span.style),
+            ];
+            for (a, b, style) in parts {
+                if a < b {
+                    result.push(Span::styled(chars[a..b].iter().collect::<String>(), style));
+                }
+            }
+        }
+        pos += len;
+    }
+
+    result
+}
+
+/// Cells `columns` of a styled line, with tabs expanded to the next tab stop.
+/// A tab or wide char cut by either edge leaves spaces in the cells it keeps.
+fn clip_columns(
+    spans: &[Span<'static>],
+    columns: Range<usize>,
+    tab_width: usize,
+) -> Vec<Span<'static>> {
+    let mut result = Vec::with_capacity(spans.len());
+    let mut col = 0;
+    for span in spans {
+        let mut content = String::new();
+        for grapheme in span.content.graphemes(true) {
+            let end = col + column::grapheme_width(grapheme, col, tab_width);
+            if col == end {
+                if columns.contains(&col) {
+                    content.push_str(grapheme);
+                }
+            } else if col >= columns.start && end <= columns.end && grapheme != "\t" {
+                content.push_str(grapheme);
+            } else {
+                let cells = end.min(columns.end).saturating_sub(col.max(columns.start));
+                content.push_str(&" ".repeat(cells));
+            }
+            col = end;
+        }
+        if !content.is_empty() {
+            result.push(Span::styled(content, span.style));
+        }
+        if col >= columns.end {
+            break;
+        }
+    }
+    result
+}
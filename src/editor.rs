@@ -6,8 +6,12 @@ use std::{
 
 use anyhow::Result;
 use crossterm::{
+    clipboard::CopyToClipboard,
     cursor::SetCursorStyle,
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyEvent, MouseEvent},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyEvent, MouseEvent,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -15,14 +19,31 @@ use ratatui::{Terminal, prelude::CrosstermBackend};
 
 use crate::{
     buffer::Buffer,
+    context::MAX_STICKY_LINES,
     diagnostic::{DiagnosticState, spawn_cargo_check},
     displayer::Displayer,
+    finder::FileFinder,
+    fold::FoldCommand,
     keyboard::{KeyboardConfig, KeyboardHandler},
     mode::EditorMode,
     mouse::{MouseConfig, MouseHandler},
+    outline::Outline,
+    project_search::{ProjectSearch, SearchFocus, replace_in_files},
+    register::{Register, Registers},
+    search::Search,
+    substitute::{ConfirmState, Replacement, Substitute, parse_range},
+    theme::Theme,
     tree::FileTree,
 };
 
+/// What the right-hand panel shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SidePanel {
+    Diagnostics,
+    Search,
+    Outline,
+}
+
 pub struct Editor {
     pub buffers: Vec<Buffer>,
     pub active_buffer: Option<usize>,
@@ -35,6 +56,26 @@ pub struct Editor {
     pub mouse_handler: MouseHandler,
     pub editor_start_x: u16,
     pub editor_max_height: u16,
+    /// Columns of text beside the gutter, as of the last frame.
+    pub editor_text_width: u16,
+    pub registers: Registers,
+    /// Register chosen with `"x` for the next yank, delete or paste.
+    pub register_name: Option<char>,
+    /// Keys typed so far of a multi-key command, e.g. `"` before a register name.
+    pub pending_keys: String,
+    /// One-off feedback shown in the status bar until the next key press.
+    pub message: Option<String>,
+    pub search: Search,
+    pub confirm: Option<ConfirmState>,
+    pub side_panel: SidePanel,
+    pub project_search: ProjectSearch,
+    pub outline: Outline,
+    pub finder: Option<FileFinder>,
+    pub theme: Theme,
+    /// Show the items enclosing the cursor above the text.
+    pub show_breadcrumb: bool,
+    /// Pin the headers of the items enclosing the top line of the view.
+    pub sticky_headers: bool,
 }
 
 impl Editor {
@@ -51,6 +92,7 @@ impl Editor {
             .canonicalize()
             .unwrap_or_else(|_| PathBuf::from(path));
 
+        let theme = Theme::vscode_dark_modern();
         let (buffers, project_dir) = if canon_path.is_dir() {
             (vec![], canon_path.clone())
         } else {
@@ -58,7 +100,7 @@ impl Editor {
                 .parent()
                 .map(|p| p.to_path_buf())
                 .unwrap_or_else(|| PathBuf::from("."));
-            (vec![Buffer::from_file(&canon_path)], project_dir)
+            (vec![Buffer::from_file(&canon_path, &theme)], project_dir)
         };
 
         let active_buffer = if buffers.is_empty() { None } else { Some(0) };
@@ -75,18 +117,34 @@ impl Editor {
             spawn_cargo_check(&diag_state, &canon_path);
         }
 
+        let file_tree = FileTree::new(&project_dir);
+
         Ok(Self {
             buffers,
             active_buffer,
             should_quit: false,
             mode,
-            file_tree: FileTree::new(&project_dir),
+            project_search: ProjectSearch::new(&file_tree.root),
+            file_tree,
             show_tree: true,
             diag_state,
             keyboard_handler: KeyboardHandler::new(key_config),
             mouse_handler: MouseHandler::new(mouse_config),
             editor_start_x: 0,
             editor_max_height: 0,
+            editor_text_width: 0,
+            registers: Registers::default(),
+            register_name: None,
+            pending_keys: String::new(),
+            message: None,
+            search: Search::default(),
+            confirm: None,
+            side_panel: SidePanel::Diagnostics,
+            outline: Outline::default(),
+            finder: None,
+            theme,
+            show_breadcrumb: true,
+            sticky_headers: false,
         })
     }
 
@@ -97,6 +155,7 @@ impl Editor {
             stdout,
             EnterAlternateScreen,
             EnableMouseCapture,
+            EnableBracketedPaste,
             SetCursorStyle::SteadyBar
         )?;
         let backend = CrosstermBackend::new(stdout);
@@ -108,24 +167,49 @@ impl Editor {
                 break;
             }
 
-            let vh = displayer.viewport_height();
+            let vh = displayer
+                .viewport_height()
+                .saturating_sub(usize::from(self.show_breadcrumb));
+            let width = self.editor_text_width as usize;
+            let sticky = self.sticky_headers;
             if let Some(buf) = self.buf_mut() {
-                buf.compute_scroll(vh);
+                buf.refresh_folds();
+                buf.compute_scroll(vh, width);
+                if sticky {
+                    buf.scroll_below_sticky(MAX_STICKY_LINES);
+                }
+            }
+            self.refresh_search();
+            self.refresh_outline();
+            if let Some(finder) = &mut self.finder {
+                finder.refresh();
             }
 
             displayer.draw(&mut self)?;
 
+            if let Some(text) = self.registers.take_clipboard() {
+                execute!(
+                    displayer.backend_mut(),
+                    CopyToClipboard::to_clipboard_from(text)
+                )?;
+            }
+
             if event::poll(Duration::from_millis(50))? {
                 let event = event::read()?;
                 self.handle_event(event)?;
             }
         }
 
+        for buf in &self.buffers {
+            let _ = buf.persist_history();
+        }
+
         disable_raw_mode()?;
         execute!(
             displayer.backend_mut(),
             LeaveAlternateScreen,
             DisableMouseCapture,
+            DisableBracketedPaste,
             SetCursorStyle::DefaultUserShape
         )?;
         Ok(())
@@ -164,6 +248,7 @@ impl Editor {
         match event {
             Event::Key(key) => self.handle_key(key)?,
             Event::Mouse(mouse) => self.handle_mouse(mouse)?,
+            Event::Paste(text) => self.handle_paste(&text),
             _ => {}
         }
         Ok(())
@@ -181,7 +266,7 @@ impl Editor {
             }
         }
 
-        self.buffers.push(Buffer::from_file(&canon));
+        self.buffers.push(Buffer::from_file(&canon, &self.theme));
         self.active_buffer = Some(self.buffers.len() - 1);
         spawn_cargo_check(&self.diag_state, &canon);
         Ok(())
@@ -191,6 +276,7 @@ impl Editor {
         if self.active_buffer.is_none() {
             return;
         }
+        let _ = self.buffers[idx].persist_history();
         self.buffers.remove(idx);
         self.active_buffer = if self.buffers.is_empty() {
             None
@@ -242,7 +328,583 @@ impl Editor {
         }
     }
 
+    pub fn undo(&mut self) {
+        if let Some(buf) = self.buf_mut() {
+            buf.undo();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(buf) = self.buf_mut() {
+            buf.redo();
+        }
+    }
+
+    pub fn start_visual(&mut self, mode: EditorMode) {
+        if let Some(buf) = self.buf_mut() {
+            buf.anchor = Some((buf.cursor_x, buf.cursor_y));
+            self.mode = mode;
+        }
+    }
+
+    pub fn exit_visual(&mut self) {
+        if let Some(buf) = self.buf_mut() {
+            buf.anchor = None;
+        }
+        self.mode = EditorMode::Nav;
+    }
+
+    /// Grow the selection to the enclosing syntax node, starting a charwise
+    /// selection from Nav mode.
+    pub fn expand_selection(&mut self) {
+        let Some(buf) = self.buf_mut() else { return };
+        if buf.expand_selection() {
+            self.mode = EditorMode::Visual;
+        }
+    }
+
+    /// Undo the last `expand_selection`, leaving Visual mode when that was
+    /// where it started.
+    pub fn shrink_selection(&mut self) {
+        let Some(buf) = self.buf_mut() else { return };
+        if buf.shrink_selection() && buf.anchor.is_none() {
+            self.mode = EditorMode::Nav;
+        }
+    }
+
+    /// Select a text object around the cursor, in Visual mode.
+    pub fn select_object(&mut self, object: &str, inside: bool) -> bool {
+        let Some(buf) = self.buf_mut() else {
+            return false;
+        };
+        if !buf.select_object(object, inside) {
+            self.message = Some(format!("no {} here", object));
+            return false;
+        }
+        self.mode = EditorMode::Visual;
+        true
+    }
+
+    /// Yank (`y`), delete (`d`) or change (`c`) a text object.
+    pub fn operate_on_object(&mut self, operator: char, object: &str, inside: bool) {
+        if !self.select_object(object, inside) {
+            return;
+        }
+        match operator {
+            'y' => self.yank_selection(),
+            'd' => self.delete_selection(),
+            'c' => self.change_selection(),
+            _ => self.exit_visual(),
+        }
+    }
+
+    /// Jump to the next (or previous) function, impl, struct or test.
+    pub fn goto_object(&mut self, object: &str, forward: bool) {
+        let Some(buf) = self.buf_mut() else { return };
+        if !buf.goto_object(object, forward) {
+            let direction = if forward { "next" } else { "previous" };
+            self.message = Some(format!("no {} {}", direction, object));
+        }
+    }
+
+    pub fn yank_selection(&mut self) {
+        let linewise = self.mode == EditorMode::VisualLine;
+        let Some(buf) = self.buf_mut() else { return };
+        let (Some(text), Some((start, _))) =
+            (buf.selected_text(linewise), buf.selection_range(linewise))
+        else {
+            return;
+        };
+
+        let line = buf.text.char_to_line(start);
+        buf.cursor_y = line;
+        buf.cursor_x = if linewise {
+            0
+        } else {
+            start - buf.text.line_to_char(line)
+        };
+
+        self.store_register(Register::new(text, linewise), "yanked");
+        self.exit_visual();
+    }
+
+    pub fn delete_selection(&mut self) {
+        let linewise = self.mode == EditorMode::VisualLine;
+        let Some(buf) = self.buf_mut() else { return };
+        let Some(text) = buf.selected_text(linewise) else {
+            return;
+        };
+        buf.delete_selection(linewise);
+        self.store_register(Register::new(text, linewise), "deleted");
+        self.exit_visual();
+    }
+
+    pub fn change_selection(&mut self) {
+        let linewise = self.mode == EditorMode::VisualLine;
+        let Some(buf) = self.buf_mut() else { return };
+        let Some(text) = buf.selected_text(linewise) else {
+            return;
+        };
+        buf.change_selection(linewise);
+        let name = self.register_name.take();
+        self.registers.store(name, Register::new(text, linewise));
+        self.mode = EditorMode::Insert;
+    }
+
+    fn store_register(&mut self, register: Register, action: &str) {
+        self.message = Some(format!("{} {}", register.summary(), action));
+        let name = self.register_name.take();
+        self.registers.store(name, register);
+    }
+
+    pub fn paste(&mut self, before: bool) {
+        let name = self.register_name.take();
+        let Some(register) = self.registers.get(name).cloned() else {
+            self.message = Some(match name {
+                Some(c) => format!("register {} is empty", c),
+                None => "nothing to paste".to_string(),
+            });
+            return;
+        };
+        if let Some(buf) = self.buf_mut() {
+            buf.paste(&register, before);
+        }
+    }
+
+    pub fn start_search(&mut self, forward: bool) {
+        let Some(i) = self.active_buffer else { return };
+        self.search.start(&self.buffers[i], forward);
+        self.mode = EditorMode::Search;
+    }
+
+    pub fn refresh_search(&mut self) {
+        if let Some(i) = self.active_buffer {
+            self.search.refresh(&self.buffers[i]);
+        }
+    }
+
+    /// Move the cursor to the match nearest to where the search started, as
+    /// the query is being typed.
+    pub fn update_search(&mut self) {
+        let Some(i) = self.active_buffer else { return };
+        let buf = &mut self.buffers[i];
+        self.search.refresh(buf);
+        (buf.cursor_x, buf.cursor_y) = self.search.origin;
+        let origin = buf.cursor_char();
+        if let Some((idx, _)) = self.search.find_from(origin, self.search.forward) {
+            buf.set_cursor_char(self.search.matches[idx].0);
+        }
+    }
+
+    pub fn cancel_search(&mut self) {
+        let origin = self.search.origin;
+        if let Some(buf) = self.buf_mut() {
+            (buf.cursor_x, buf.cursor_y) = origin;
+        }
+        self.search.clear();
+        self.mode = EditorMode::Nav;
+    }
+
+    /// Jump to the next match in the search direction, or the opposite one
+    /// with `reverse`.
+    pub fn search_next(&mut self, reverse: bool) {
+        let Some(i) = self.active_buffer else { return };
+        let buf = &mut self.buffers[i];
+        self.search.refresh(buf);
+        let forward = self.search.forward != reverse;
+        match self.search.find_from(buf.cursor_char(), forward) {
+            Some((idx, wrapped)) => {
+                buf.set_cursor_char(self.search.matches[idx].0);
+                if wrapped {
+                    self.message = Some("search wrapped".to_string());
+                }
+            }
+            None if !self.search.query.is_empty() => {
+                self.message = Some(format!("pattern not found: {}", self.search.query));
+            }
+            None => {}
+        }
+    }
+
+    /// Run a `:s` command, or start stepping through its matches when it has
+    /// the `c` flag.
+    pub fn substitute(&mut self, sub: Substitute) {
+        let Some(buf) = self.buf_mut() else { return };
+        let replacements = sub.replacements(buf);
+        let Some(first) = replacements.first() else {
+            self.message = Some("pattern not found".to_string());
+            return;
+        };
+
+        if sub.confirm {
+            buf.set_cursor_char(first.start);
+            self.confirm = Some(ConfirmState {
+                candidates: replacements,
+                ..ConfirmState::default()
+            });
+            self.mode = EditorMode::Confirm;
+        } else {
+            self.apply_replacements(replacements);
+        }
+    }
+
+    /// Handle an answer to the current `:s///c` prompt: `y`es, `n`o, `a`ll
+    /// remaining, `l`ast (this one, then stop) or `q`uit.
+    pub fn answer_confirm(&mut self, answer: char) {
+        let Some(confirm) = self.confirm.as_mut() else {
+            self.mode = EditorMode::Nav;
+            return;
+        };
+
+        let more = match answer {
+            'y' => confirm.answer(true),
+            'n' => confirm.answer(false),
+            'l' => {
+                confirm.answer(true);
+                false
+            }
+            'a' => {
+                confirm.accept_rest();
+                false
+            }
+            'q' => false,
+            _ => true,
+        };
+
+        if more {
+            if let Some(start) = confirm.current().map(|r| r.start)
+                && let Some(buf) = self.buf_mut()
+            {
+                buf.set_cursor_char(start);
+            }
+            return;
+        }
+
+        let accepted = self.confirm.take().map(|c| c.accepted).unwrap_or_default();
+        self.mode = EditorMode::Nav;
+        self.apply_replacements(accepted);
+    }
+
+    fn apply_replacements(&mut self, replacements: Vec<Replacement>) {
+        let Some(buf) = self.buf_mut() else { return };
+        let mut lines: Vec<usize> = replacements
+            .iter()
+            .map(|r| buf.text.char_to_line(r.start))
+            .collect();
+        lines.dedup();
+        let ranges: Vec<_> = replacements
+            .into_iter()
+            .map(|r| (r.start, r.end, r.text))
+            .collect();
+
+        buf.replace_ranges(&ranges);
+        self.message = Some(format!(
+            "{} substitution{} on {} line{}",
+            ranges.len(),
+            if ranges.len() > 1 { "s" } else { "" },
+            lines.len(),
+            if lines.len() > 1 { "s" } else { "" },
+        ));
+    }
+
+    /// Show the project search panel and focus its query input.
+    pub fn open_project_search(&mut self) {
+        self.side_panel = SidePanel::Search;
+        self.project_search.focus = SearchFocus::Query;
+        self.mode = EditorMode::ProjectSearch;
+    }
+
+    pub fn close_project_search(&mut self) {
+        self.side_panel = SidePanel::Diagnostics;
+        self.mode = self.resting_mode();
+    }
+
+    /// Show the outline in the side panel and give it the keys, with the
+    /// symbol under the cursor selected. Closes it when it already has them.
+    pub fn toggle_outline(&mut self) {
+        if self.mode == EditorMode::Outline {
+            self.close_outline();
+            return;
+        }
+        let Some(i) = self.active_buffer else { return };
+        let buf = &self.buffers[i];
+        self.outline.refresh(buf);
+        self.outline
+            .select_current(buf.text.char_to_byte(buf.cursor_char()));
+        self.side_panel = SidePanel::Outline;
+        self.mode = EditorMode::Outline;
+    }
+
+    pub fn close_outline(&mut self) {
+        self.side_panel = SidePanel::Diagnostics;
+        self.mode = self.resting_mode();
+    }
+
+    /// Keep the outline in step with the active buffer while it is shown.
+    pub fn refresh_outline(&mut self) {
+        if self.side_panel == SidePanel::Outline
+            && let Some(i) = self.active_buffer
+        {
+            self.outline.refresh(&self.buffers[i]);
+        }
+    }
+
+    /// Move the cursor to the selected symbol, leaving the outline open.
+    pub fn open_outline_symbol(&mut self) {
+        let Some(symbol) = self.outline.selected_symbol().cloned() else {
+            return;
+        };
+        if let Some(buf) = self.buf_mut() {
+            buf.jump_to_line(symbol.line);
+            buf.cursor_x = symbol.column.min(buf.visible_line_len(buf.cursor_y));
+        }
+        self.mode = EditorMode::Nav;
+    }
+
+    /// Mode to fall back to when a popup or panel is dismissed.
+    fn resting_mode(&self) -> EditorMode {
+        if self.active_buffer.is_some() {
+            EditorMode::Nav
+        } else {
+            EditorMode::TreeNav
+        }
+    }
+
+    /// Index the project files and show the fuzzy finder.
+    pub fn open_finder(&mut self) {
+        self.finder = Some(FileFinder::new(&self.file_tree.root));
+        self.mode = EditorMode::Finder;
+    }
+
+    pub fn close_finder(&mut self) {
+        self.finder = None;
+        self.mode = self.resting_mode();
+    }
+
+    pub fn open_finder_selection(&mut self) -> Result<()> {
+        let path = self.finder.as_ref().and_then(|f| f.selected_path());
+        self.close_finder();
+        if let Some(path) = path {
+            self.open_file(&path)?;
+            self.mode = EditorMode::Nav;
+        }
+        Ok(())
+    }
+
+    /// Open the file of the selected hit with the cursor on the match.
+    pub fn open_search_hit(&mut self) -> Result<()> {
+        let Some(hit) = self.project_search.selected_hit() else {
+            return Ok(());
+        };
+        self.open_file(&hit.path)?;
+        if let Some(buf) = self.buf_mut() {
+            buf.jump_to_line(hit.line);
+            buf.cursor_x = hit.column.min(buf.visible_line_len(buf.cursor_y));
+        }
+        self.mode = EditorMode::Nav;
+        Ok(())
+    }
+
+    /// Apply the replacements enabled in the preview: open buffers get a
+    /// single undoable edit each, other files are rewritten on disk. The
+    /// search and the diagnostics are rerun afterwards.
+    pub fn apply_project_replace(&mut self) -> Result<()> {
+        let mut plan = self.project_search.planned_replacements();
+        let check_target = self
+            .buf()
+            .and_then(|b| b.filepath.clone())
+            .or_else(|| plan.keys().next().cloned());
+        let mut applied = 0;
+        let mut files = 0;
+
+        for buf in &mut self.buffers {
+            let Some(replacements) = buf.filepath.as_ref().and_then(|p| plan.remove(p)) else {
+                continue;
+            };
+            let ranges: Vec<_> = replacements
+                .into_iter()
+                .filter_map(|(hit, new)| {
+                    if hit.line >= buf.text.len_lines() {
+                        return None;
+                    }
+                    // Skip hits whose line was edited since the search.
+                    let line = buf.text.line(hit.line).to_string();
+                    if line.trim_end_matches(['\n', '\r']) != hit.text {
+                        return None;
+                    }
+                    let line_start = buf.text.line_to_char(hit.line);
+                    let start = line_start + hit.text[..hit.range.0].chars().count();
+                    let end = line_start + hit.text[..hit.range.1].chars().count();
+                    Some((start, end, new))
+                })
+                .collect();
+            if !ranges.is_empty() {
+                applied += ranges.len();
+                files += 1;
+                buf.replace_ranges(&ranges);
+            }
+        }
+
+        let (disk_applied, disk_files) = replace_in_files(&plan)?;
+        applied += disk_applied;
+        files += disk_files;
+
+        self.message = Some(format!(
+            "{} replacement{} in {} file{}",
+            applied,
+            if applied == 1 { "" } else { "s" },
+            files,
+            if files == 1 { "" } else { "s" },
+        ));
+        self.project_search.run();
+        if let Some(path) = check_target {
+            spawn_cargo_check(&self.diag_state, &path);
+        }
+        Ok(())
+    }
+
+    /// Apply a `:set` option.
+    pub fn set_option(&mut self, option: &str) {
+        match option {
+            "clipboard" => self.registers.osc52 = true,
+            "noclipboard" => self.registers.osc52 = false,
+            "breadcrumb" => self.show_breadcrumb = true,
+            "nobreadcrumb" => self.show_breadcrumb = false,
+            "sticky" => self.sticky_headers = true,
+            "nosticky" => self.sticky_headers = false,
+            "wrap" | "nowrap" => {
+                if let Some(buf) = self.buf_mut() {
+                    buf.wrap = option == "wrap";
+                }
+            }
+            "expandtab" | "noexpandtab" => {
+                if let Some(buf) = self.buf_mut() {
+                    buf.indent.tabs = option == "noexpandtab";
+                }
+            }
+            other if let Some((name, value)) = other.split_once('=') => {
+                let Some(buf) = self.buf_mut() else { return };
+                let Some(n) = value.parse::<usize>().ok().filter(|&n| n > 0) else {
+                    self.message = Some(format!("invalid value: {}", other));
+                    return;
+                };
+                match name {
+                    "shiftwidth" | "sw" => buf.indent.width = n,
+                    "tabstop" | "ts" => buf.indent.tab_width = n,
+                    _ => self.message = Some(format!("unknown option: {}", name)),
+                }
+            }
+            other => self.message = Some(format!("unknown option: {}", other)),
+        }
+    }
+
+    /// Switch to the theme called `name`, or list the themes when empty.
+    pub fn set_theme(&mut self, name: &str) {
+        if name.is_empty() {
+            self.message = Some(format!(
+                "theme {} (available: {})",
+                self.theme.name,
+                Theme::available().join(", ")
+            ));
+            return;
+        }
+        match Theme::load(name) {
+            Ok(theme) => {
+                for buf in &mut self.buffers {
+                    buf.highlighter.set_theme(&theme);
+                }
+                self.theme = theme;
+            }
+            Err(e) => self.message = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Run a fold command on the active buffer. Closing the fold around the
+    /// cursor moves it to the first line of the fold.
+    pub fn fold(&mut self, command: FoldCommand) {
+        let Some(buf) = self.buf_mut() else { return };
+        buf.refresh_folds();
+        let found = buf.folds.apply(command, buf.cursor_y);
+        buf.cursor_y = buf.folds.visible_row(buf.cursor_y);
+        buf.cursor_x = buf.cursor_x.min(buf.visible_line_len(buf.cursor_y));
+        if !found {
+            self.message = Some("no fold found".to_string());
+        }
+    }
+
+    pub fn indent_selection(&mut self, outdent: bool) {
+        let Some(buf) = self.buf_mut() else { return };
+        if let Some((top, bottom)) = buf.selection_lines() {
+            if outdent {
+                buf.outdent_lines(top, bottom);
+            } else {
+                buf.indent_lines(top, bottom);
+            }
+        }
+        self.exit_visual();
+    }
+
+    /// Lines addressed by a `:reindent` command, e.g. `%reindent` or
+    /// `3,10reindent`. A bare `reindent` covers the whole buffer.
+    pub fn reindent_range(&self, command: &str) -> Option<(usize, usize)> {
+        let buf = self.buf()?;
+        let last_line = buf.text.len_lines() - 1;
+        if command == "reindent" {
+            return Some((0, last_line));
+        }
+        match parse_range(command, buf.cursor_y, last_line)? {
+            ((top, bottom), "reindent") => Some((top, bottom.min(last_line))),
+            _ => None,
+        }
+    }
+
+    pub fn reindent_selection(&mut self) {
+        if let Some((top, bottom)) = self.buf().and_then(|buf| buf.selection_lines()) {
+            self.reindent_lines(top, bottom);
+        }
+        self.exit_visual();
+    }
+
+    pub fn reindent_lines(&mut self, top: usize, bottom: usize) {
+        let Some(buf) = self.buf_mut() else { return };
+        if buf
+            .highlighter
+            .language()
+            .is_none_or(|lang| lang.indents.is_none())
+        {
+            self.message = Some(format!(
+                "no indent rules for {}",
+                buf.highlighter.language_name()
+            ));
+            return;
+        }
+        let changed = buf.reindent(top, bottom);
+        self.message = Some(format!(
+            "{} line{} reindented",
+            changed,
+            if changed == 1 { "" } else { "s" }
+        ));
+    }
+
+    /// Pasted text arrives in one piece with bracketed paste, so it is
+    /// inserted as is rather than replayed through the typed-key path.
+    fn handle_paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        match &mut self.mode {
+            EditorMode::Insert => {
+                if let Some(buf) = self.buf_mut() {
+                    buf.insert_text(&text);
+                }
+            }
+            EditorMode::Command { command_str, .. } => {
+                command_str.push_str(text.lines().next().unwrap_or(""));
+            }
+            _ => {}
+        }
+    }
+
     fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        self.message = None;
         let keyboard_handler = std::mem::take(&mut self.keyboard_handler);
         keyboard_handler.handle_key(key, self)?;
         self.keyboard_handler = keyboard_handler;
@@ -0,0 +1,219 @@
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Numeric ranges such as `{1..3}` are spelled out up to this many numbers.
+const MAX_RANGE_LEN: i64 = 1000;
+
+/// A `[glob]` section of an `.editorconfig` file.
+struct Section {
+    /// None when the glob doesn't compile, so the section matches nothing.
+    glob: Option<Regex>,
+    properties: Vec<(String, String)>,
+}
+
+/// Properties the `.editorconfig` files above `path` set for it, with keys
+/// and values lowercased. Files are looked up from the file's directory up
+/// to the first one marked `root = true`; closer files and later sections
+/// win, and `unset` clears a property.
+pub fn properties(path: &Path) -> HashMap<String, String> {
+    let mut files = Vec::new();
+    let mut dir = path.parent().map(|p| p.to_path_buf());
+    while let Some(d) = dir {
+        if let Ok(content) = std::fs::read_to_string(d.join(".editorconfig")) {
+            let (root, sections) = parse(&content, &d);
+            files.push(sections);
+            if root {
+                break;
+            }
+        }
+        dir = d.parent().map(|p| p.to_path_buf());
+    }
+
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut properties = HashMap::new();
+    for section in files.iter().rev().flatten() {
+        if !section
+            .glob
+            .as_ref()
+            .is_some_and(|glob| glob.is_match(&path))
+        {
+            continue;
+        }
+        for (key, value) in &section.properties {
+            if value == "unset" {
+                properties.remove(key);
+            } else {
+                properties.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    properties
+}
+
+/// Whether the file says `root = true`, and its sections with globs
+/// resolved against `dir`.
+fn parse(content: &str, dir: &Path) -> (bool, Vec<Section>) {
+    let mut root = false;
+    let mut sections: Vec<Section> = Vec::new();
+    let mut in_preamble = true;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_preamble = false;
+            sections.push(Section {
+                glob: Regex::new(&glob_to_regex(glob, dir)).ok(),
+                properties: Vec::new(),
+            });
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim().to_lowercase(), value.trim().to_lowercase());
+        if in_preamble {
+            root |= key == "root" && value == "true";
+        } else if let Some(section) = sections.last_mut() {
+            section.properties.push((key, value));
+        }
+    }
+    (root, sections)
+}
+
+/// Regex matching the full paths `glob` applies to. Globs without a `/` match
+/// file names in any directory below `dir`, others paths relative to it.
+fn glob_to_regex(glob: &str, dir: &Path) -> String {
+    let dir = PathBuf::from(dir).to_string_lossy().replace('\\', "/");
+    let dir = regex::escape(dir.trim_end_matches('/'));
+    let (prefix, glob) = match glob.strip_prefix('/') {
+        Some(rest) => ("/", rest),
+        None if glob.contains('/') => ("/", glob),
+        None => ("/(?:.*/)?", glob),
+    };
+
+    let chars: Vec<char> = glob.chars().collect();
+    let mut out = String::new();
+    let mut braces = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                out.push_str(".*");
+                i += 1;
+            }
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(len) => {
+                    let class: String = chars[i + 1..i + 1 + len].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(negated) => format!("^{}", negated),
+                        None => class,
+                    };
+                    out.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+                    i += len + 1;
+                }
+                None => out.push_str("\\["),
+            },
+            '{' => match chars[i + 1..].iter().position(|&c| c == '}') {
+                Some(len) if number_range(&chars[i + 1..i + 1 + len]).is_some() => {
+                    let (lo, hi) = number_range(&chars[i + 1..i + 1 + len]).expect("checked");
+                    let numbers: Vec<String> = (lo..=hi).map(|n| n.to_string()).collect();
+                    out.push_str(&format!("(?:{})", numbers.join("|")));
+                    i += len + 1;
+                }
+                _ => {
+                    braces += 1;
+                    out.push_str("(?:");
+                }
+            },
+            '}' if braces > 0 => {
+                braces -= 1;
+                out.push(')');
+            }
+            ',' if braces > 0 => out.push('|'),
+            '\\' if i + 1 < chars.len() => {
+                out.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 1;
+            }
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    format!("^{}{}{}$", dir, prefix, out)
+}
+
+/// Bounds of a `{lo..hi}` range, given the text between the braces.
+fn number_range(chars: &[char]) -> Option<(i64, i64)> {
+    let text: String = chars.iter().collect();
+    let (lo, hi) = text.split_once("..")?;
+    let (lo, hi): (i64, i64) = (lo.parse().ok()?, hi.parse().ok()?);
+    let (lo, hi) = (lo.min(hi), lo.max(hi));
+    (hi - lo < MAX_RANGE_LEN).then_some((lo, hi))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        Regex::new(&glob_to_regex(glob, Path::new("/p")))
+            .unwrap()
+            .is_match(path)
+    }
+
+    #[test]
+    fn file_name_globs() {
+        assert!(matches("*.rs", "/p/main.rs"));
+        assert!(matches("*.rs", "/p/src/deep/main.rs"));
+        assert!(!matches("*.rs", "/p/main.rsx"));
+        assert!(!matches("*.rs", "/q/main.rs"));
+        assert!(matches("?.md", "/p/a.md"));
+        assert!(!matches("?.md", "/p/ab.md"));
+    }
+
+    #[test]
+    fn path_globs() {
+        assert!(matches("src/*.rs", "/p/src/main.rs"));
+        assert!(!matches("src/*.rs", "/p/src/deep/main.rs"));
+        assert!(!matches("src/*.rs", "/p/lib/src/main.rs"));
+        assert!(matches("/Makefile", "/p/Makefile"));
+        assert!(!matches("/Makefile", "/p/sub/Makefile"));
+        assert!(matches("src/**.rs", "/p/src/deep/main.rs"));
+        assert!(matches("**/test/*.rs", "/p/a/b/test/x.rs"));
+    }
+
+    #[test]
+    fn braces_and_classes() {
+        assert!(matches("*.{rs,toml}", "/p/Cargo.toml"));
+        assert!(matches("*.{rs,toml}", "/p/main.rs"));
+        assert!(!matches("*.{rs,toml}", "/p/README.md"));
+        assert!(matches("file{1..3}.txt", "/p/file2.txt"));
+        assert!(!matches("file{1..3}.txt", "/p/file4.txt"));
+        assert!(matches("file{3..1}.txt", "/p/file1.txt"));
+        assert!(matches("[ab].txt", "/p/a.txt"));
+        assert!(!matches("[ab].txt", "/p/c.txt"));
+        assert!(matches("[!ab].txt", "/p/c.txt"));
+        assert!(!matches("[!ab].txt", "/p/a.txt"));
+        assert!(matches(r"\{x\}.txt", "/p/{x}.txt"));
+    }
+
+    #[test]
+    fn sections_and_root() {
+        let (root, sections) = parse(
+            "root = true\n\n[*]\nindent_style = Tab\n; comment\n[*.md]\nindent_size = 2\n",
+            Path::new("/p"),
+        );
+        assert!(root);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(
+            sections[0].properties,
+            vec![("indent_style".to_string(), "tab".to_string())]
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::{theme::UiTheme, tree::walk_files_by_dir};
+
+/// Lines of the selected file shown in the preview pane.
+const PREVIEW_LINES: usize = 200;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_SEGMENT_START: i64 = 8;
+const BONUS_WORD_START: i64 = 6;
+const BONUS_CAMEL: i64 = 5;
+const BONUS_FILE_NAME: i64 = 2;
+const PENALTY_GAP: i64 = 1;
+
+struct Match {
+    score: i64,
+    file: usize,
+    /// Char positions of the matched query chars, for highlighting.
+    positions: Vec<usize>,
+}
+
+/// Files found so far by the background walk of the project.
+#[derive(Default)]
+struct FinderState {
+    /// Paths relative to the root, in the order they were found.
+    files: Vec<String>,
+    is_running: bool,
+    /// Set when the finder closes, so its walk stops early.
+    cancelled: bool,
+}
+
+/// Ctrl+P style popup: fuzzy matches the query against every file under
+/// the project root. The project is walked in the background and the list
+/// fills in as files are found.
+pub struct FileFinder {
+    root: PathBuf,
+    /// Paths relative to `root`, taken from `state` by `refresh`.
+    files: Vec<String>,
+    state: Arc<Mutex<FinderState>>,
+    is_running: bool,
+    pub query: String,
+    matches: Vec<Match>,
+    pub selected: usize,
+    preview: Vec<String>,
+}
+
+impl FileFinder {
+    pub fn new(root: &Path) -> Self {
+        let state = Arc::new(Mutex::new(FinderState {
+            is_running: true,
+            ..FinderState::default()
+        }));
+        let thread_state = Arc::clone(&state);
+        let thread_root = root.to_path_buf();
+        thread::spawn(move || run_file_walk(thread_state, thread_root));
+
+        Self {
+            root: root.to_path_buf(),
+            files: Vec::new(),
+            state,
+            is_running: true,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            preview: Vec::new(),
+        }
+    }
+
+    /// Take the files the background walk found since the last call and
+    /// score them, keeping the selected file selected.
+    pub fn refresh(&mut self) {
+        let Ok(state) = self.state.lock() else {
+            return;
+        };
+        self.is_running = state.is_running;
+        if state.files.len() == self.files.len() {
+            return;
+        }
+        let from = self.files.len();
+        self.files.extend_from_slice(&state.files[from..]);
+        drop(state);
+
+        let selected = self.matches.get(self.selected).map(|m| m.file);
+        let query = self.query_chars();
+        self.matches.extend(self.score(&query, from));
+        self.sort_matches();
+        self.selected = selected
+            .and_then(|file| self.matches.iter().position(|m| m.file == file))
+            .unwrap_or(0);
+        if selected.is_none() {
+            self.load_preview();
+        }
+    }
+
+    /// Rescore every file against the query and reset the selection to the
+    /// best match.
+    pub fn update(&mut self) {
+        let query = self.query_chars();
+        self.matches = self.score(&query, 0);
+        self.sort_matches();
+        self.selected = 0;
+        self.load_preview();
+    }
+
+    fn query_chars(&self) -> Vec<char> {
+        self.query.chars().filter(|c| !c.is_whitespace()).collect()
+    }
+
+    /// Matches of `query` among the files from index `from` on.
+    fn score(&self, query: &[char], from: usize) -> Vec<Match> {
+        self.files
+            .iter()
+            .enumerate()
+            .skip(from)
+            .filter_map(|(file, path)| {
+                fuzzy_match(query, path).map(|(score, positions)| Match {
+                    score,
+                    file,
+                    positions,
+                })
+            })
+            .collect()
+    }
+
+    fn sort_matches(&mut self) {
+        self.matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| self.files[a.file].len().cmp(&self.files[b.file].len()))
+                .then_with(|| self.files[a.file].cmp(&self.files[b.file]))
+        });
+    }
+
+    pub fn move_up(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            self.load_preview();
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+            self.load_preview();
+        }
+    }
+
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        self.matches
+            .get(self.selected)
+            .map(|m| self.root.join(&self.files[m.file]))
+    }
+
+    fn load_preview(&mut self) {
+        self.preview = self
+            .selected_path()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .take(PREVIEW_LINES)
+                    .map(|l| l.replace('\t', "    "))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, ui: &UiTheme) {
+        let popup = centered(area, 80, 70);
+        f.render_widget(Clear, popup);
+
+        let block = Block::default()
+            .title(format!(
+                " Find file ({}/{}{}) ",
+                self.matches.len(),
+                self.files.len(),
+                if self.is_running { "…" } else { "" }
+            ))
+            .borders(Borders::ALL)
+            .border_style(ui.panel_focused);
+        let inner = block.inner(popup);
+        f.render_widget(block, popup);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(1)])
+            .split(inner);
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[1]);
+
+        f.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("> ", ui.list_prompt),
+                Span::styled(self.query.clone(), ui.list_input),
+            ])),
+            rows[0],
+        );
+
+        let height = panes[0].height as usize;
+        let scroll = self.selected.saturating_sub(height.saturating_sub(1));
+        let list: Vec<Line> = self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(scroll)
+            .take(height)
+            .map(|(i, m)| {
+                let base = if i == self.selected {
+                    ui.list_selected
+                } else {
+                    ui.list_item
+                };
+                let spans: Vec<Span> = self.files[m.file]
+                    .chars()
+                    .enumerate()
+                    .map(|(ci, c)| {
+                        let style = if m.positions.contains(&ci) {
+                            base.patch(ui.list_match)
+                        } else {
+                            base
+                        };
+                        Span::styled(c.to_string(), style)
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect();
+        f.render_widget(Paragraph::new(list), panes[0]);
+
+        let preview: Vec<Line> = self
+            .preview
+            .iter()
+            .take(panes[1].height as usize)
+            .map(|l| Line::from(Span::styled(l.clone(), ui.list_detail)))
+            .collect();
+        f.render_widget(
+            Paragraph::new(preview).block(
+                Block::default()
+                    .borders(Borders::LEFT)
+                    .border_style(ui.border),
+            ),
+            panes[1],
+        );
+    }
+}
+
+impl Drop for FileFinder {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.cancelled = true;
+        }
+    }
+}
+
+fn run_file_walk(state: Arc<Mutex<FinderState>>, root: PathBuf) {
+    walk_files_by_dir(&root, |found| {
+        let Ok(mut s) = state.lock() else {
+            return false;
+        };
+        if s.cancelled {
+            return false;
+        }
+        s.files.extend(found.iter().map(|p| {
+            p.strip_prefix(&root)
+                .unwrap_or(p)
+                .to_string_lossy()
+                .to_string()
+        }));
+        true
+    });
+    if let Ok(mut s) = state.lock() {
+        s.is_running = false;
+    }
+}
+
+fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    // Widened so the product can't overflow on large terminals.
+    let scale =
+        |len: u16, percent: u16| (u32::from(len) * u32::from(percent.min(100)) / 100) as u16;
+    let width = scale(area.width, percent_x);
+    let height = scale(area.height, percent_y);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Bonus for matching the char at `i`, based on what precedes it.
+fn position_bonus(chars: &[char], i: usize, file_name_start: usize) -> i64 {
+    let prev = if i == 0 { '/' } else { chars[i - 1] };
+    let boundary = match prev {
+        '/' | '\\' => BONUS_SEGMENT_START,
+        '_' | '-' | '.' | ' ' => BONUS_WORD_START,
+        p if p.is_lowercase() && chars[i].is_uppercase() => BONUS_CAMEL,
+        _ => 0,
+    };
+    let in_file_name = if i >= file_name_start {
+        BONUS_FILE_NAME
+    } else {
+        0
+    };
+    boundary + in_file_name
+}
+
+/// Score `candidate` against `query` as a case-insensitive subsequence.
+/// Returns the best score and the matched char positions, or `None` when
+/// the query isn't a subsequence of the candidate.
+fn fuzzy_match(query: &[char], candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = candidate.chars().collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let (n, m) = (chars.len(), query.len());
+    if m > n {
+        return None;
+    }
+
+    let file_name_start = chars
+        .iter()
+        .rposition(|c| *c == '/' || *c == '\\')
+        .map_or(0, |i| i + 1);
+    let eq = |q: char, c: char| q.to_lowercase().eq(c.to_lowercase());
+
+    // score[i][j]: best score with query[i] matched at chars[j].
+    let mut score = vec![vec![None::<i64>; n]; m];
+    let mut from = vec![vec![0usize; n]; m];
+
+    for j in 0..n {
+        if eq(query[0], chars[j]) {
+            score[0][j] = Some(SCORE_MATCH + position_bonus(&chars, j, file_name_start));
+        }
+    }
+
+    for i in 1..m {
+        // Best `score[i - 1][k] + PENALTY_GAP * k` over k < j - 1, so the
+        // gap penalty can be applied in O(1) per cell.
+        let mut best_gapped: Option<(i64, usize)> = None;
+        for j in i..n {
+            if j >= 2
+                && let Some(s) = score[i - 1][j - 2]
+            {
+                let v = s + PENALTY_GAP * (j - 2) as i64;
+                if best_gapped.is_none_or(|(b, _)| v > b) {
+                    best_gapped = Some((v, j - 2));
+                }
+            }
+            if !eq(query[i], chars[j]) {
+                continue;
+            }
+
+            let base = SCORE_MATCH + position_bonus(&chars, j, file_name_start);
+            let consecutive = score[i - 1][j - 1].map(|s| (s + BONUS_CONSECUTIVE, j - 1));
+            let gapped = best_gapped.map(|(v, k)| (v - PENALTY_GAP * (j - 1) as i64, k));
+            let best = match (consecutive, gapped) {
+                (Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+                (a, b) => a.or(b),
+            };
+            if let Some((s, k)) = best {
+                score[i][j] = Some(s + base);
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let (end, best) = score[m - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|(_, s)| *s)?;
+
+    let mut positions = vec![end; m];
+    for i in (1..m).rev() {
+        positions[i - 1] = from[i][positions[i]];
+    }
+    Some((best, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(query: &str, candidate: &str) -> Option<i64> {
+        let query: Vec<char> = query.chars().collect();
+        fuzzy_match(&query, candidate).map(|(score, _)| score)
+    }
+
+    fn positions(query: &str, candidate: &str) -> Vec<usize> {
+        let query: Vec<char> = query.chars().collect();
+        fuzzy_match(&query, candidate).unwrap().1
+    }
+
+    #[test]
+    fn subsequence_only() {
+        assert!(score("mn", "src/main.rs").is_some());
+        assert!(score("MAIN", "src/main.rs").is_some());
+        assert_eq!(score("nm", "main"), None);
+        assert_eq!(score("long query", "short"), None);
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn ranks_better_matches_higher() {
+        // Consecutive chars beat scattered ones.
+        assert!(score("main", "src/main.rs") > score("main", "src/my_api_init.rs"));
+        // Matches in the file name beat matches in directories.
+        assert!(score("edit", "src/editor.rs") > score("edit", "edit/src/lib.rs"));
+        // Word and segment starts beat matches inside words.
+        assert!(score("fb", "foo_bar.rs") > score("fb", "xfxb.rs"));
+        assert!(score("fb", "src/fooBar.rs") > score("fb", "src/foobar.rs"));
+    }
+
+    #[test]
+    fn prefers_positions_at_word_starts() {
+        assert_eq!(positions("fb", "foo_bar.rs"), vec![0, 4]);
+        assert_eq!(positions("rs", "src/main.rs"), vec![9, 10]);
+    }
+}
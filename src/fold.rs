@@ -0,0 +1,348 @@
+use ropey::Rope;
+use std::collections::BTreeSet;
+use tree_sitter::{self as ts, StreamingIterator};
+
+use crate::highlighter::Highlighter;
+
+/// A fold command, bound to `z` keys in Nav mode and to `:fold` commands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FoldCommand {
+    Toggle,
+    Close,
+    Open,
+    ToggleAll,
+    CloseAll,
+    OpenAll,
+}
+
+impl FoldCommand {
+    /// Command for the key typed after `z`, as in vim.
+    pub fn from_key(key: char) -> Option<Self> {
+        Some(match key {
+            'a' => Self::Toggle,
+            'c' => Self::Close,
+            'o' => Self::Open,
+            'A' => Self::ToggleAll,
+            'M' => Self::CloseAll,
+            'R' => Self::OpenAll,
+            _ => return None,
+        })
+    }
+
+    pub fn from_command(command: &str) -> Option<Self> {
+        Some(match command {
+            "fold" => Self::Close,
+            "unfold" => Self::Open,
+            "foldtoggle" => Self::Toggle,
+            "foldall" => Self::CloseAll,
+            "unfoldall" => Self::OpenAll,
+            "foldtoggleall" => Self::ToggleAll,
+            _ => return None,
+        })
+    }
+}
+
+/// Foldable line ranges of a buffer, and which of them are folded away.
+///
+/// A closed fold keeps its first row on screen and hides the rest.
+#[derive(Debug, Default)]
+pub struct Folds {
+    /// `(first, last)` rows of each region, sorted by first row. When several
+    /// regions start on the same row only the largest is kept.
+    regions: Vec<(usize, usize)>,
+    /// First rows of the closed regions.
+    closed: BTreeSet<usize>,
+    /// Buffer revision the regions were computed for.
+    computed_for: Option<u64>,
+}
+
+impl Folds {
+    /// Recompute the regions from the highlighter's tree, using the `folds`
+    /// query of its language, if the buffer changed since the last call.
+    /// Closed folds whose first row no longer starts a region are opened.
+    pub fn refresh(&mut self, highlighter: &Highlighter, text: &Rope, revision: u64) {
+        if self.computed_for == Some(revision) {
+            return;
+        }
+        self.computed_for = Some(revision);
+        self.regions = regions(highlighter, text);
+        let regions = &self.regions;
+        self.closed
+            .retain(|row| regions.binary_search_by_key(row, |r| r.0).is_ok());
+    }
+
+    /// Keep regions and closed folds on their rows across an edit replacing
+    /// rows `start..=old_end` with `start..=new_end`, until the next
+    /// `refresh`. Folds starting on a removed row are opened.
+    pub fn shift(&mut self, start: usize, old_end: usize, new_end: usize) {
+        let shifted = |row: usize| {
+            if row > old_end {
+                row + new_end - old_end
+            } else {
+                row.min(new_end.max(start))
+            }
+        };
+        let kept = |row: usize| row <= start || row > old_end;
+        self.closed = std::mem::take(&mut self.closed)
+            .into_iter()
+            .filter(|&row| kept(row))
+            .map(shifted)
+            .collect();
+        self.regions = std::mem::take(&mut self.regions)
+            .into_iter()
+            .filter(|&(first, _)| kept(first))
+            .map(|(first, last)| (shifted(first), shifted(last)))
+            .filter(|&(first, last)| last > first)
+            .collect();
+    }
+
+    /// Region starting on `row`.
+    pub fn region_at(&self, row: usize) -> Option<(usize, usize)> {
+        let i = self.regions.binary_search_by_key(&row, |r| r.0).ok()?;
+        Some(self.regions[i])
+    }
+
+    pub fn is_closed(&self, row: usize) -> bool {
+        self.closed.contains(&row)
+    }
+
+    /// Last row hidden by the closed fold starting on `row`.
+    pub fn hidden_end(&self, row: usize) -> Option<usize> {
+        if !self.is_closed(row) {
+            return None;
+        }
+        self.region_at(row).map(|(_, last)| last)
+    }
+
+    /// Row drawn for `row`: the first row of the outermost closed fold
+    /// hiding it, or `row` itself.
+    pub fn visible_row(&self, row: usize) -> usize {
+        self.closed
+            .range(..row)
+            .find(|&&first| self.hidden_end(first).is_some_and(|last| row <= last))
+            .copied()
+            .unwrap_or(row)
+    }
+
+    /// Row drawn on the display line below `row`'s.
+    pub fn next_visible(&self, row: usize) -> usize {
+        let row = self.visible_row(row);
+        self.hidden_end(row).unwrap_or(row) + 1
+    }
+
+    /// Row drawn on the display line above `row`'s, or the first row drawn
+    /// when there is none.
+    pub fn prev_visible(&self, row: usize) -> usize {
+        self.visible_row(self.visible_row(row).saturating_sub(1))
+    }
+
+    /// Close the innermost open region containing `row`.
+    pub fn close(&mut self, row: usize) -> bool {
+        let innermost = self
+            .regions
+            .iter()
+            .filter(|&&(first, last)| first <= row && row <= last && !self.is_closed(first))
+            .min_by_key(|&&(first, last)| last - first);
+        match innermost {
+            Some(&(first, _)) => self.closed.insert(first),
+            None => false,
+        }
+    }
+
+    /// Open every closed fold containing `row`.
+    pub fn open(&mut self, row: usize) -> bool {
+        let containing: Vec<usize> = self
+            .closed
+            .range(..=row)
+            .filter(|&&first| self.hidden_end(first).is_some_and(|last| row <= last))
+            .copied()
+            .collect();
+        for first in &containing {
+            self.closed.remove(first);
+        }
+        !containing.is_empty()
+    }
+
+    /// Open folds hiding `row`, leaving one whose first row it is closed.
+    pub fn reveal(&mut self, row: usize) {
+        loop {
+            let first = self.visible_row(row);
+            if first == row {
+                break;
+            }
+            self.closed.remove(&first);
+        }
+    }
+
+    /// Run `command` with the cursor on `row`. Returns false when there was
+    /// no fold to act on.
+    pub fn apply(&mut self, command: FoldCommand, row: usize) -> bool {
+        match command {
+            FoldCommand::Toggle => self.toggle(row),
+            FoldCommand::Close => self.close(row),
+            FoldCommand::Open => self.open(row),
+            FoldCommand::ToggleAll => self.toggle_all(),
+            FoldCommand::CloseAll => self.close_all(),
+            FoldCommand::OpenAll => self.open_all(),
+        }
+    }
+
+    /// Open the fold on `row` if it is closed, otherwise close the innermost
+    /// one around it.
+    fn toggle(&mut self, row: usize) -> bool {
+        if self.is_closed(row) {
+            self.open(row)
+        } else {
+            self.close(row)
+        }
+    }
+
+    pub fn close_all(&mut self) -> bool {
+        self.closed = self.regions.iter().map(|&(first, _)| first).collect();
+        !self.closed.is_empty()
+    }
+
+    pub fn open_all(&mut self) -> bool {
+        let any = !self.closed.is_empty();
+        self.closed.clear();
+        any
+    }
+
+    /// Open everything if anything is folded, otherwise fold everything.
+    pub fn toggle_all(&mut self) -> bool {
+        if self.closed.is_empty() {
+            self.close_all()
+        } else {
+            self.open_all()
+        }
+    }
+}
+
+/// Fold regions of the current tree. Consecutive `@fold.run` nodes of the
+/// same kind, such as a block of line comments, make up a single region.
+fn regions(highlighter: &Highlighter, text: &Rope) -> Vec<(usize, usize)> {
+    let (Some(tree), Some(query)) = (
+        highlighter.tree(),
+        highlighter.language().and_then(|lang| lang.folds.as_ref()),
+    ) else {
+        return Vec::new();
+    };
+    let run_index = query.capture_index_for_name("fold.run");
+
+    let mut regions = Vec::new();
+    let mut run: Option<(&str, usize, usize)> = None;
+    let mut cursor = ts::QueryCursor::new();
+    let mut captures = cursor.captures(query, tree.root_node(), |node: ts::Node| {
+        text.byte_slice(node.byte_range())
+            .chunks()
+            .map(str::as_bytes)
+    });
+    while let Some((m, index)) = captures.next() {
+        let capture = m.captures[*index];
+        let node = capture.node;
+        let first = node.start_position().row;
+        // Nodes ending with their line break stop on the line before.
+        let end = node.end_position();
+        let last = if end.column == 0 && end.row > first {
+            end.row - 1
+        } else {
+            end.row
+        };
+
+        if Some(capture.index) != run_index {
+            regions.push((first, last));
+            continue;
+        }
+        match &mut run {
+            Some((kind, _, run_last)) if *kind == node.kind() && first <= *run_last + 1 => {
+                *run_last = last.max(*run_last);
+            }
+            _ => {
+                regions.extend(run.map(|(_, first, last)| (first, last)));
+                run = Some((node.kind(), first, last));
+            }
+        }
+    }
+    regions.extend(run.map(|(_, first, last)| (first, last)));
+
+    regions.retain(|&(first, last)| last > first);
+    regions.sort_unstable_by_key(|&(first, last)| (first, std::cmp::Reverse(last)));
+    regions.dedup_by_key(|r| r.0);
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{language, theme::Theme};
+
+    /// Regions of a function on rows 0..=6 holding a block on rows 2..=4.
+    fn nested() -> Folds {
+        Folds {
+            regions: vec![(0, 6), (2, 4)],
+            ..Folds::default()
+        }
+    }
+
+    #[test]
+    fn close_innermost_then_outer() {
+        let mut folds = nested();
+        assert!(folds.close(3));
+        assert!(folds.is_closed(2));
+        assert!(folds.close(3));
+        assert!(folds.is_closed(0));
+        assert!(!folds.close(3));
+        assert!(folds.open(3));
+        assert!(!folds.is_closed(0) && !folds.is_closed(2));
+    }
+
+    #[test]
+    fn moves_over_closed_folds() {
+        let mut folds = nested();
+        folds.close(2);
+        assert_eq!(folds.visible_row(3), 2);
+        assert_eq!(folds.next_visible(2), 5);
+        assert_eq!(folds.prev_visible(5), 2);
+        assert_eq!(folds.prev_visible(0), 0);
+
+        folds.close(0);
+        assert_eq!(folds.visible_row(3), 0);
+        assert_eq!(folds.next_visible(0), 7);
+        assert_eq!(folds.prev_visible(7), 0);
+    }
+
+    #[test]
+    fn reveal_opens_only_what_hides_the_row() {
+        let mut folds = nested();
+        folds.close_all();
+        folds.reveal(3);
+        assert!(!folds.is_closed(0) && !folds.is_closed(2));
+        folds.close_all();
+        folds.reveal(2);
+        assert!(!folds.is_closed(0) && folds.is_closed(2));
+    }
+
+    #[test]
+    fn shift_follows_edits() {
+        let mut folds = nested();
+        folds.close(3);
+        // Two lines inserted after row 1, above the inner block.
+        folds.shift(1, 1, 3);
+        assert_eq!(folds.regions, vec![(0, 8), (4, 6)]);
+        assert!(folds.is_closed(4));
+        // The line the inner block starts on is joined with the one above.
+        folds.shift(3, 4, 3);
+        assert_eq!(folds.regions, vec![(0, 7)]);
+        assert!(!folds.is_closed(3) && !folds.is_closed(4));
+    }
+
+    #[test]
+    fn regions_from_the_tree() {
+        let source = "// a\n// b\nfn f() {\n    if x {\n        y();\n    }\n}\n";
+        let text = Rope::from_str(source);
+        let rust = language::by_name("rust");
+        let mut highlighter = Highlighter::new(rust, &Theme::vscode_dark_modern());
+        highlighter.update(&text);
+        assert_eq!(regions(&highlighter, &text), vec![(0, 1), (2, 6), (3, 5)]);
+    }
+}
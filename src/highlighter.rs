@@ -1,213 +1,301 @@
-use ratatui::{
-    style::{Color, Modifier, Style},
-    text::Span,
+use ratatui::{style::Style, text::Span};
+use ropey::Rope;
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::HashMap,
+    ops::{Range, RangeInclusive},
+    rc::Rc,
 };
-use std::collections::HashMap;
-use tree_sitter as ts;
+use tree_sitter::{self as ts, StreamingIterator};
 
-struct Theme {
-    styles: HashMap<&'static str, Style>,
-    default: Style,
-}
+use crate::{
+    language::{self, Language},
+    theme::Theme,
+};
 
-impl Theme {
-    fn vscode_dark_modern() -> Self {
-        let mut styles = HashMap::new();
-
-        // ── VS Code Dark Modern — faithful palette ──────────────────────
-        let keyword = Color::Rgb(86, 156, 214); // #569CD6  — keywords
-        let control = Color::Rgb(197, 134, 192); // #C586C0  — control flow
-        let function = Color::Rgb(220, 220, 170); // #DCDCAA  — functions / macros
-        let type_c = Color::Rgb(78, 201, 176); // #4EC9B0  — types / traits
-        let string = Color::Rgb(206, 145, 120); // #CE9178  — strings
-        let escape = Color::Rgb(215, 186, 125); // #D7BA7D  — escape sequences
-        let number = Color::Rgb(181, 206, 168); // #B5CEA8  — numeric literals
-        let comment = Color::Rgb(106, 153, 85); // #6A9955  — comments (italic)
-        let doc_com = Color::Rgb(127, 178, 103); // #7FB267  — doc comments (brighter green)
-        let variable = Color::Rgb(156, 220, 254); // #9CDCFE  — variables / params
-        let constant = Color::Rgb(79, 193, 255); // #4FC1FF  — constants / enum variants
-        let attribute = Color::Rgb(156, 220, 254); // #9CDCFE  — attributes
-        let lifetime = Color::Rgb(86, 156, 214); // #569CD6  — lifetimes
-        let punct = Color::Rgb(212, 212, 212); // #D4D4D4  — punctuation / operators
-        let default = Color::Rgb(212, 212, 212); // #D4D4D4  — plain text
-        let namespace = Color::Rgb(78, 201, 176); // #4EC9B0  — modules / crates
-
-        // Helper — VS Code Dark Modern uses NO bold for most tokens
-        let s = |fg: Color| Style::default().fg(fg);
-        let si = |fg: Color| Style::default().fg(fg).add_modifier(Modifier::ITALIC);
-
-        // ── Keywords ────────────────────────────────────────────────────
-        for kw in [
-            "fn", "let", "use", "mod", "pub", "struct", "enum", "impl", "trait", "where", "as",
-            "in", "ref", "const", "static", "type", "unsafe", "async", "await", "move", "dyn",
-            "crate", "super", "extern", "mut",
-        ] {
-            styles.insert(kw, s(keyword));
-        }
+/// Injections nested deeper than this are not parsed.
+const MAX_INJECTION_DEPTH: usize = 3;
 
-        // ── Control flow ────────────────────────────────────────────────
-        for kw in [
-            "if", "else", "match", "for", "while", "loop", "return", "break", "continue",
-        ] {
-            styles.insert(kw, s(control));
-        }
+/// Lines searched in each direction when gathering a combined injection.
+const MAX_COMBINED_LINES: usize = 2000;
 
-        // ── Tree-sitter node kinds ──────────────────────────────────────
-
-        // Generic keyword fallback
-        styles.insert("keyword", s(keyword));
-
-        // Functions
-        styles.insert("function", s(function));
-        styles.insert("function.method", s(function));
-
-        // Types
-        styles.insert("type", s(type_c));
-        styles.insert("type_identifier", s(type_c));
-        styles.insert("primitive_type", s(type_c));
-        styles.insert("scoped_type_identifier", s(type_c));
-        styles.insert("generic_type", s(type_c));
-
-        // Strings
-        styles.insert("string_literal", s(string));
-        styles.insert("string_content", s(string));
-        styles.insert("char_literal", s(string));
-        styles.insert("raw_string_literal", s(string));
-        styles.insert("escape_sequence", s(escape));
-
-        // Numbers
-        styles.insert("integer_literal", s(number));
-        styles.insert("float_literal", s(number));
-
-        // Booleans — VS Code treats true/false as keyword-blue
-        styles.insert("boolean_literal", s(keyword));
-        styles.insert("true", s(keyword));
-        styles.insert("false", s(keyword));
-
-        // Comments — italic, no bold
-        styles.insert("line_comment", si(comment));
-        styles.insert("block_comment", si(comment));
-        styles.insert("doc_comment", si(doc_com));
-
-        // Identifiers
-        styles.insert("identifier", s(variable));
-        styles.insert("field_identifier", s(variable));
-        styles.insert("shorthand_field_identifier", s(variable));
-
-        // Constants / enum variants
-        styles.insert("enum_variant", s(constant));
-
-        // Attributes
-        styles.insert("attribute_item", si(attribute));
-        styles.insert("inner_attribute_item", si(attribute));
-        styles.insert("attribute", si(attribute));
-
-        // Macros
-        styles.insert("macro_invocation", s(function));
-        styles.insert("macro_definition", s(function));
-
-        // Lifetimes
-        styles.insert("lifetime", s(lifetime));
-
-        // self / Self
-        styles.insert("self", s(keyword));
-        styles.insert("metavariable", s(keyword));
-
-        // mut specifier
-        styles.insert("mutable_specifier", s(keyword));
-
-        // Modules / namespaces
-        styles.insert("scoped_identifier", s(namespace));
-
-        // Operators / punctuation — same as default text
-        styles.insert("operator", s(punct));
-        styles.insert("::", s(punct));
-        styles.insert("->", s(punct));
-        styles.insert("=>", s(punct));
-        styles.insert("&", s(punct));
-        styles.insert("*", s(punct));
-        styles.insert("!", s(punct));
-
-        // Punctuation brackets
-        styles.insert("(", s(punct));
-        styles.insert(")", s(punct));
-        styles.insert("{", s(punct));
-        styles.insert("}", s(punct));
-        styles.insert("[", s(punct));
-        styles.insert("]", s(punct));
-        styles.insert("<", s(punct));
-        styles.insert(">", s(punct));
-        styles.insert(",", s(punct));
-        styles.insert(";", s(punct));
-        styles.insert(":", s(punct));
-        styles.insert(".", s(punct));
-
-        Self {
-            styles,
-            default: Style::default().fg(default),
-        }
-    }
+/// Styled `(start, end)` byte columns of one line, sorted by start.
+type LineStyles = Vec<(usize, usize, Style)>;
 
-    fn style_for(&self, node_kind: &str) -> Style {
-        self.styles.get(node_kind).copied().unwrap_or(self.default)
-    }
+/// Identifies a layer by language and the byte span it covers.
+type LayerKey = (&'static str, usize, usize);
+
+/// A region of the buffer parsed with another language, e.g. the Markdown of
+/// a doc comment.
+struct Layer {
+    language: &'static Language,
+    tree: ts::Tree,
+    ranges: Vec<ts::Range>,
+}
+
+/// Where a combined injection was found: matches of one pattern of `host`'s
+/// injection query on consecutive `rows`, parsed together as one layer.
+struct CombinedSpan {
+    host: &'static str,
+    pattern: usize,
+    rows: RangeInclusive<usize>,
+    key: LayerKey,
+}
+
+/// One match of an injection query.
+struct Injection {
+    pattern: usize,
+    /// Language named by the query; `None` means the host's own host, as for
+    /// a Markdown code fence without an info string inside a doc comment.
+    language: Option<String>,
+    content: Vec<ts::Range>,
+    combined: bool,
 }
 
 pub struct Highlighter {
     parser: ts::Parser,
     tree: Option<ts::Tree>,
-    theme: Theme,
-    source_cache: String,
+    /// Style of text no capture covers.
+    default: Style,
+    /// `None` for plain text, which is never parsed.
+    language: Option<&'static Language>,
+    /// Style of each highlight capture, per language id.
+    capture_styles: HashMap<&'static str, Vec<Style>>,
+    /// Snapshot of the text the current tree was parsed from. Cloning a rope
+    /// is cheap, it only bumps a reference count.
+    source: Rope,
+    /// Styled byte ranges per line, filled lazily as lines are drawn. `None`
+    /// means the line must be recomputed from the tree.
+    line_cache: RefCell<Vec<Option<LineStyles>>>,
+    /// Rows touched by edits since the last `update`.
+    edited_rows: Vec<usize>,
+    /// Injected layers parsed for the current tree, built as lines are drawn.
+    layers: RefCell<HashMap<LayerKey, Rc<Layer>>>,
+    combined: RefCell<Vec<CombinedSpan>>,
+    injection_parser: RefCell<ts::Parser>,
 }
 
 impl Highlighter {
-    pub fn new() -> Self {
+    pub fn new(language: Option<&'static Language>, theme: &Theme) -> Self {
         let mut parser = ts::Parser::new();
-        let language = tree_sitter_rust::LANGUAGE;
-        parser
-            .set_language(&language.into())
-            .expect("Error loading Rust grammar");
+        // Grammars are built against the tree-sitter we link, so this only
+        // fails on an ABI mismatch; plain text is the safe fallback.
+        let language = language.filter(|lang| parser.set_language(&lang.grammar).is_ok());
 
-        Self {
+        let mut highlighter = Self {
             parser,
             tree: None,
-            theme: Theme::vscode_dark_modern(),
-            source_cache: String::new(),
+            default: theme.default,
+            language,
+            capture_styles: HashMap::new(),
+            source: Rope::new(),
+            line_cache: RefCell::new(Vec::new()),
+            edited_rows: Vec::new(),
+            layers: RefCell::new(HashMap::new()),
+            combined: RefCell::new(Vec::new()),
+            injection_parser: RefCell::new(ts::Parser::new()),
+        };
+        highlighter.set_theme(theme);
+        highlighter
+    }
+
+    /// Restyle with `theme`. The tree is kept; only cached lines are dropped.
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.default = theme.default;
+        self.capture_styles = language::languages()
+            .iter()
+            .map(|lang| {
+                let styles = lang
+                    .highlights
+                    .capture_names()
+                    .iter()
+                    .map(|name| theme.style_for(name))
+                    .collect();
+                (lang.id, styles)
+            })
+            .collect();
+        for line in self.line_cache.get_mut() {
+            *line = None;
         }
     }
 
-    pub fn update(&mut self, source: &str) {
-        self.source_cache = source.to_string();
-        self.tree = self.parser.parse(source, None);
+    /// Language of the buffer, `None` for plain text.
+    pub fn language(&self) -> Option<&'static Language> {
+        self.language
+    }
+
+    /// Syntax tree of the text passed to the last `update`.
+    pub fn tree(&self) -> Option<&ts::Tree> {
+        self.tree.as_ref()
+    }
+
+    /// Name of the language being highlighted, for the status bar.
+    pub fn language_name(&self) -> &'static str {
+        self.language.map_or("Plain Text", |lang| lang.name)
+    }
+
+    /// Tell the current tree about an edit so the next `update` can reuse
+    /// the unchanged parts of it.
+    pub fn edit(&mut self, edit: &ts::InputEdit) {
+        if let Some(tree) = &mut self.tree {
+            tree.edit(edit);
+        }
+
+        // Keep the cache aligned with the new line numbering; the edited
+        // lines themselves are always recomputed.
+        let cache = self.line_cache.get_mut();
+        let start = edit.start_position.row.min(cache.len());
+        let old_end = (edit.old_end_position.row + 1).clamp(start, cache.len());
+        let new_lines = edit.new_end_position.row - edit.start_position.row + 1;
+        cache.splice(start..old_end, std::iter::repeat_n(None, new_lines));
+
+        let (old_last, new_last) = (edit.old_end_position.row, edit.new_end_position.row);
+        for row in &mut self.edited_rows {
+            if *row > old_last {
+                *row = *row + new_last - old_last;
+            } else if *row > new_last {
+                *row = new_last;
+            }
+        }
+        self.edited_rows
+            .extend(edit.start_position.row..=edit.new_end_position.row);
+
+        // Layers after the edit move with the text; the ones it touches are
+        // dropped and parsed again when next drawn.
+        let layers = std::mem::take(self.layers.get_mut());
+        for ((id, start, end), layer) in layers {
+            if end < edit.start_byte {
+                self.layers.get_mut().insert((id, start, end), layer);
+            } else if start > edit.old_end_byte {
+                let mut tree = layer.tree.clone();
+                tree.edit(edit);
+                let shifted = Layer {
+                    language: layer.language,
+                    tree,
+                    ranges: layer.ranges.iter().map(|r| shift_range(r, edit)).collect(),
+                };
+                let key = (id, shift_byte(start, edit), shift_byte(end, edit));
+                self.layers.get_mut().insert(key, Rc::new(shifted));
+            }
+        }
+        let layers = self.layers.get_mut();
+        self.combined.get_mut().retain_mut(|span| {
+            if *span.rows.start() > old_last {
+                span.rows =
+                    span.rows.start() + new_last - old_last..=span.rows.end() + new_last - old_last;
+                span.key = (
+                    span.key.0,
+                    shift_byte(span.key.1, edit),
+                    shift_byte(span.key.2, edit),
+                );
+            } else if *span.rows.end() >= edit.start_position.row {
+                return false;
+            }
+            layers.contains_key(&span.key)
+        });
+    }
+
+    /// Reparse `source`, reading straight from the rope chunks. Edits must
+    /// have been reported through `edit` beforehand.
+    pub fn update(&mut self, source: &Rope) {
+        self.source = source.clone();
+        let mut dirty = std::mem::take(&mut self.edited_rows);
+        let Some(language) = self.language else {
+            return;
+        };
+        let mut read = |byte: usize, _: ts::Point| -> &[u8] {
+            if byte >= source.len_bytes() {
+                return &[];
+            }
+            let (chunk, chunk_start, _, _) = source.chunk_at_byte(byte);
+            &chunk.as_bytes()[byte - chunk_start..]
+        };
+        let old_tree = self.tree.take();
+        self.tree = self
+            .parser
+            .parse_with_options(&mut read, old_tree.as_ref(), None);
+
+        let cache = self.line_cache.get_mut();
+        match (&old_tree, &self.tree) {
+            (Some(old), Some(new)) => {
+                for range in old.changed_ranges(new) {
+                    dirty.extend(range.start_point.row..=range.end_point.row);
+                }
+            }
+            _ => {
+                cache.clear();
+                self.layers.get_mut().clear();
+                self.combined.get_mut().clear();
+            }
+        }
+        cache.resize(source.len_lines(), None);
+        dirty.sort_unstable();
+        dirty.dedup();
+
+        // Layers next to a change may have grown, shrunk or changed language,
+        // e.g. a new `///` line joining a doc comment; only those are parsed
+        // again. The rest stay as they were.
+        let near_dirty = |rows: RangeInclusive<usize>| {
+            let from = dirty.partition_point(|&row| row + 1 < *rows.start());
+            dirty.get(from).is_some_and(|&row| row <= rows.end() + 1)
+        };
+        self.layers.get_mut().retain(|_, layer| {
+            let first = layer.ranges.first().map_or(0, |r| r.start_point.row);
+            let last = layer.ranges.last().map_or(0, |r| r.end_point.row);
+            !near_dirty(first..=last)
+        });
+        let layers = self.layers.get_mut();
+        self.combined
+            .get_mut()
+            .retain(|span| !near_dirty(span.rows.clone()) && layers.contains_key(&span.key));
+
+        // An edit inside a combined injection can restyle all of it, e.g.
+        // opening a code fence in a doc comment, so the whole run is redrawn.
+        // Neighbouring rows are included for edits that split or join runs.
+        if let Some(tree) = &self.tree {
+            let mut spans = Vec::new();
+            for &row in &dirty {
+                for row in row.saturating_sub(1)..=row + 1 {
+                    spans.extend(self.combined_rows(language, tree, row));
+                }
+            }
+            let cache = self.line_cache.get_mut();
+            for row in dirty.into_iter().chain(spans.into_iter().flatten()) {
+                if let Some(line) = cache.get_mut(row) {
+                    *line = None;
+                }
+            }
+        }
     }
 
     pub fn highlight_line(&self, line_idx: usize, line_text: &str) -> Vec<Span<'static>> {
-        let Some(tree) = &self.tree else {
-            return vec![Span::raw(line_text.to_string())];
+        let (Some(tree), Some(language)) = (&self.tree, self.language) else {
+            return vec![Span::styled(line_text.to_string(), self.default)];
         };
 
-        let mut spans: Vec<(usize, usize, Style)> = Vec::new();
-        self.collect_leaf_styles(tree.root_node(), line_idx, &mut spans);
+        let mut cache = self.line_cache.borrow_mut();
+        let mut uncached = None;
+        let spans = match cache.get_mut(line_idx) {
+            Some(line) => line,
+            None => &mut uncached,
+        }
+        .get_or_insert_with(|| self.compute_line(tree, language, line_idx));
 
         if spans.is_empty() {
-            return vec![Span::styled(line_text.to_string(), self.theme.default)];
+            return vec![Span::styled(line_text.to_string(), self.default)];
         }
 
-        spans.sort_by_key(|(start, _, _)| *start);
-
         let len = line_text.len();
         let mut result = Vec::new();
         let mut pos = 0;
 
-        for (start, end, style) in &spans {
+        for (start, end, style) in spans.iter() {
             let s = (*start).min(len);
             let e = (*end).min(len);
 
             if s > pos {
-                result.push(Span::styled(
-                    line_text[pos..s].to_string(),
-                    self.theme.default,
-                ));
+                result.push(Span::styled(line_text[pos..s].to_string(), self.default));
             }
             if e > s && s >= pos {
                 result.push(Span::styled(line_text[s..e].to_string(), *style));
@@ -216,184 +304,339 @@ impl Highlighter {
         }
 
         if pos < len {
-            result.push(Span::styled(
-                line_text[pos..].to_string(),
-                self.theme.default,
-            ));
+            result.push(Span::styled(line_text[pos..].to_string(), self.default));
         }
 
         if result.is_empty() {
-            vec![Span::styled(line_text.to_string(), self.theme.default)]
+            vec![Span::styled(line_text.to_string(), self.default)]
         } else {
             result
         }
     }
 
-    /// Resolve the style for a leaf node with semantic context from its parent.
-    ///
-    /// This is the key to getting close to VS Code's behaviour: tree-sitter
-    /// gives us syntax nodes, but the *meaning* often depends on where the
-    /// identifier sits in the tree (function name vs variable vs type …).
-    fn resolve_semantic_style(&self, node: ts::Node) -> Style {
-        let kind = node.kind();
-
-        // 1. Direct match on the node kind (keywords, literals, comments …)
-        if let Some(&s) = self.theme.styles.get(kind) {
-            // For "identifier" we want to fall through to semantic checks
-            if kind == "identifier" {
-                // fall through
-            } else if kind == "line_comment" || kind == "block_comment" {
-                // Distinguish doc comments (/// //! /** /*!)
-                let text = node.utf8_text(self.source_cache.as_bytes()).unwrap_or("");
-                if text.starts_with("///")
-                    || text.starts_with("//!")
-                    || text.starts_with("/**")
-                    || text.starts_with("/*!")
-                {
-                    return self.theme.style_for("doc_comment");
-                }
-                return s;
-            } else {
-                return s;
-            }
+    /// Highlight one line: the buffer's own language first, then every
+    /// injected layer touching the line drawn on top of it.
+    fn compute_line(
+        &self,
+        tree: &ts::Tree,
+        language: &'static Language,
+        line_idx: usize,
+    ) -> LineStyles {
+        let source = &self.source;
+        if line_idx >= source.len_lines() {
+            return Vec::new();
         }
+        let line = source.line_to_byte(line_idx)..source.line_to_byte(line_idx + 1);
 
-        // 2. Semantic disambiguation via parent (and grandparent)
-        if let Some(parent) = node.parent() {
-            let pk = parent.kind();
-            let gp_kind = parent.parent().map(|gp| gp.kind());
-
-            // Helper: is this node the "name" child that sits right before
-            // an argument list inside a call_expression?
-            let is_call_target = |p: &str, gp: Option<&str>| -> bool {
-                // Direct: call_expression > identifier
-                if p == "call_expression" {
-                    return true;
-                }
-                // Scoped: call_expression > scoped_identifier > identifier "new"
-                //   e.g. Vec::new(), String::from()
-                if p == "scoped_identifier" && gp == Some("call_expression") {
-                    // Only the last segment (the function name)
-                    return node.next_sibling().is_none();
-                }
-                // Generic: call_expression > generic_function > identifier
-                if p == "generic_function" && gp == Some("call_expression") {
-                    return true;
-                }
-                if p == "generic_function" {
-                    return true;
-                }
-                false
-            };
-
-            match (pk, kind) {
-                // ── Function definitions ────────────────────────────
-                ("function_item", "identifier") => {
-                    return self.theme.style_for("function");
-                }
+        let mut paint: Vec<Option<Style>> = vec![None; line.len()];
+        self.paint_layer(language, tree, None, None, &line, &mut paint, 0);
 
-                // ── Function / method calls ─────────────────────────
-                (_, "identifier") if is_call_target(pk, gp_kind) => {
-                    return self.theme.style_for("function");
-                }
+        let mut spans: LineStyles = Vec::new();
+        for (col, cell) in paint.into_iter().enumerate() {
+            let Some(style) = cell else { continue };
+            match spans.last_mut() {
+                Some((_, end, last)) if *end == col && *last == style => *end += 1,
+                _ => spans.push((col, col + 1, style)),
+            }
+        }
+        spans
+    }
 
-                // ── Macro name (before the `!`) ────────────────────
-                ("macro_invocation", "identifier") => {
-                    return self.theme.style_for("macro_invocation");
-                }
+    /// Paint the highlight captures of one layer over the bytes of `line`,
+    /// clipped to the layer's `ranges`, then recurse into its injections.
+    ///
+    /// Captures are painted outermost first, so a node's style shows through
+    /// wherever no descendant is captured. When several patterns capture the
+    /// same node, the first pattern in the query wins.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_layer(
+        &self,
+        language: &'static Language,
+        tree: &ts::Tree,
+        ranges: Option<&[ts::Range]>,
+        host: Option<&'static Language>,
+        line: &Range<usize>,
+        paint: &mut [Option<Style>],
+        depth: usize,
+    ) {
+        let Some(styles) = self.capture_styles.get(language.id) else {
+            return;
+        };
+        let source = &self.source;
+
+        let mut captures = Vec::new();
+        let mut cursor = ts::QueryCursor::new();
+        cursor.set_byte_range(line.clone());
+        let text = |node: ts::Node| source.byte_slice(node.byte_range()).chunks();
+        let mut matches = cursor.captures(&language.highlights, tree.root_node(), text);
+        while let Some((m, idx)) = matches.next() {
+            let capture = m.captures[*idx];
+            let node = capture.node;
+            captures.push((
+                node.start_byte(),
+                node.end_byte(),
+                m.pattern_index,
+                capture.index as usize,
+            ));
+        }
+        captures.sort_by_key(|&(start, end, pattern, _)| (start, Reverse(end), pattern));
 
-                // ── Methods via field_expression ────────────────────
-                // e.g. foo.bar()  →  call_expression > field_expression > field_identifier "bar"
-                // e.g. foo.bar    →  field_expression > field_identifier "bar"  (field access)
-                ("field_expression", "field_identifier") => {
-                    if gp_kind == Some("call_expression") {
-                        return self.theme.style_for("function");
-                    }
-                    return self.theme.style_for("field_identifier");
+        let whole = [line.clone()];
+        let clips: Vec<Range<usize>> = match ranges {
+            Some(ranges) => ranges.iter().map(|r| r.start_byte..r.end_byte).collect(),
+            None => whole.to_vec(),
+        };
+        let mut last = None;
+        for (start, end, _, capture) in captures {
+            if last == Some((start, end)) {
+                continue;
+            }
+            last = Some((start, end));
+            for clip in &clips {
+                let from = start.max(clip.start).max(line.start) - line.start;
+                let to = end.min(clip.end).min(line.end).saturating_sub(line.start);
+                for cell in paint.iter_mut().take(to).skip(from) {
+                    *cell = Some(cell.unwrap_or(self.default).patch(styles[capture]));
                 }
+            }
+        }
 
-                // ── Types ──────────────────────────────────────────
-                ("type_identifier", _)
-                | ("scoped_type_identifier", "identifier")
-                | ("struct_item", "type_identifier")
-                | ("enum_item", "type_identifier")
-                | ("impl_item", "type_identifier")
-                | ("trait_item", "type_identifier")
-                | ("type_arguments", "type_identifier")
-                | ("function_item", "type_identifier") => {
-                    return self.theme.style_for("type");
-                }
+        if depth >= MAX_INJECTION_DEPTH {
+            return;
+        }
+        let row = source.byte_to_line(line.start);
+        for injection in self.injections(language, tree, ranges, line.clone()) {
+            if let Some(layer) = self.layer_for(language, tree, ranges, host, injection, row) {
+                let ranges = Some(layer.ranges.as_slice());
+                self.paint_layer(
+                    layer.language,
+                    &layer.tree,
+                    ranges,
+                    Some(language),
+                    line,
+                    paint,
+                    depth + 1,
+                );
+            }
+        }
+    }
 
-                // use declarations — colour path segments as namespace/type
-                ("use_declaration", _)
-                | ("use_as_clause", "identifier")
-                | ("scoped_use_list", "identifier")
-                | ("use_wildcard", _)
-                | ("use_list", "identifier") => {
-                    return self.theme.style_for("type");
+    /// Matches of `language`'s injection query within `bytes`, with their
+    /// content clipped to the layer's own `ranges`.
+    fn injections(
+        &self,
+        language: &'static Language,
+        tree: &ts::Tree,
+        ranges: Option<&[ts::Range]>,
+        bytes: Range<usize>,
+    ) -> Vec<Injection> {
+        let Some(query) = &language.injections else {
+            return Vec::new();
+        };
+        let Some(content_idx) = query.capture_index_for_name("injection.content") else {
+            return Vec::new();
+        };
+        let language_idx = query.capture_index_for_name("injection.language");
+        let source = &self.source;
+
+        let mut found = Vec::new();
+        let mut cursor = ts::QueryCursor::new();
+        cursor.set_byte_range(bytes);
+        let text = |node: ts::Node| source.byte_slice(node.byte_range()).chunks();
+        let mut matches = cursor.matches(query, tree.root_node(), text);
+        while let Some(m) = matches.next() {
+            let settings = query.property_settings(m.pattern_index);
+            let setting = |key: &str| settings.iter().find(|p| &*p.key == key);
+            let named = setting("injection.language").and_then(|p| p.value.as_deref());
+            let captured = m
+                .captures
+                .iter()
+                .find(|c| Some(c.index) == language_idx)
+                .map(|c| source.byte_slice(c.node.byte_range()).to_string());
+            let language = named.map(str::to_string).or(captured).map(|name| {
+                // Info strings like `rust,ignore` carry attributes after the name.
+                name.split(',').next().unwrap_or("").trim().to_string()
+            });
+
+            let mut content = Vec::new();
+            for c in m.captures.iter().filter(|c| c.index == content_idx) {
+                let node = c.node.range();
+                match ranges {
+                    None => content.push(node),
+                    Some(ranges) => content.extend(ranges.iter().filter_map(|r| {
+                        let start = node.start_byte.max(r.start_byte);
+                        let end = node.end_byte.min(r.end_byte);
+                        (start < end).then(|| self.byte_range(start, end))
+                    })),
                 }
+            }
+            if !content.is_empty() {
+                found.push(Injection {
+                    pattern: m.pattern_index,
+                    language,
+                    content,
+                    combined: setting("injection.combined").is_some(),
+                });
+            }
+        }
+        found
+    }
 
-                // Scoped identifiers: std::io::Result, MyEnum::Variant
-                // If inside a call_expression the last segment was already
-                // caught above; otherwise treat as type/namespace.
-                ("scoped_identifier", "identifier") => {
-                    return self.theme.style_for("type");
-                }
+    /// The parsed layer for an injection found on `row`, parsing it on first
+    /// use. Combined injections gather the same pattern's matches on the rows
+    /// around `row` into a single layer.
+    fn layer_for(
+        &self,
+        host: &'static Language,
+        tree: &ts::Tree,
+        ranges: Option<&[ts::Range]>,
+        outer: Option<&'static Language>,
+        injection: Injection,
+        row: usize,
+    ) -> Option<Rc<Layer>> {
+        let language = match &injection.language {
+            Some(name) => language::by_name(name)?,
+            None => outer?,
+        };
 
-                // ── Parameters ─────────────────────────────────────
-                ("parameter", "identifier") | ("closure_parameters", "identifier") => {
-                    return self.theme.style_for("identifier");
-                }
+        let mut content = injection.content;
+        let mut rows = row..=row;
+        if injection.combined {
+            let known = self.combined.borrow().iter().find_map(|span| {
+                (span.host == host.id
+                    && span.pattern == injection.pattern
+                    && span.rows.contains(&row))
+                .then_some(span.key)
+            });
+            if let Some(key) = known {
+                return self.layers.borrow().get(&key).cloned();
+            }
 
-                _ => {}
+            let last_row = self.source.len_lines().saturating_sub(1);
+            let (mut first, mut last) = (row, row);
+            let mut neighbour = |row: usize| {
+                let bytes = self.source.line_to_byte(row)..self.source.line_to_byte(row + 1);
+                let found = self
+                    .injections(host, tree, ranges, bytes)
+                    .into_iter()
+                    .find(|i| i.combined && i.pattern == injection.pattern);
+                found.map(|i| content.extend(i.content)).is_some()
+            };
+            while first > 0 && row - first < MAX_COMBINED_LINES && neighbour(first - 1) {
+                first -= 1;
+            }
+            while last < last_row && last - row < MAX_COMBINED_LINES && neighbour(last + 1) {
+                last += 1;
             }
+            rows = first..=last;
         }
-
-        // 3. Fallback — known identifier → variable colour, else default
-        if kind == "identifier" {
-            return self.theme.style_for("identifier");
+        content.sort_by_key(|r| r.start_byte);
+        content.dedup_by_key(|r| r.start_byte);
+
+        let key = (
+            language.id,
+            content.first()?.start_byte,
+            content.last()?.end_byte,
+        );
+        if injection.combined {
+            self.combined.borrow_mut().push(CombinedSpan {
+                host: host.id,
+                pattern: injection.pattern,
+                rows,
+                key,
+            });
+        }
+        if let Some(layer) = self.layers.borrow().get(&key) {
+            return Some(layer.clone());
         }
 
-        self.theme.default
+        let source = &self.source;
+        let mut read = |byte: usize, _: ts::Point| -> &[u8] {
+            if byte >= source.len_bytes() {
+                return &[];
+            }
+            let (chunk, chunk_start, _, _) = source.chunk_at_byte(byte);
+            &chunk.as_bytes()[byte - chunk_start..]
+        };
+        let mut parser = self.injection_parser.borrow_mut();
+        parser.set_language(&language.grammar).ok()?;
+        parser.set_included_ranges(&content).ok()?;
+        let tree = parser.parse_with_options(&mut read, None, None)?;
+
+        let layer = Rc::new(Layer {
+            language,
+            tree,
+            ranges: content,
+        });
+        self.layers.borrow_mut().insert(key, layer.clone());
+        Some(layer)
     }
 
-    fn collect_leaf_styles(
+    /// Rows covered by the combined injections found on `row` of the
+    /// buffer's own tree.
+    fn combined_rows(
         &self,
-        node: ts::Node,
-        line_idx: usize,
-        spans: &mut Vec<(usize, usize, Style)>,
-    ) {
-        let start_line = node.start_position().row;
-        let end_line = node.end_position().row;
-
-        // Prune branches that don't touch this line
-        if end_line < line_idx || start_line > line_idx {
-            return;
+        language: &'static Language,
+        tree: &ts::Tree,
+        row: usize,
+    ) -> Vec<RangeInclusive<usize>> {
+        if row >= self.source.len_lines() {
+            return Vec::new();
         }
+        let bytes = self.source.line_to_byte(row)..self.source.line_to_byte(row + 1);
+        for injection in self.injections(language, tree, None, bytes) {
+            if injection.combined {
+                self.layer_for(language, tree, None, None, injection, row);
+            }
+        }
+        self.combined
+            .borrow()
+            .iter()
+            .filter(|span| span.rows.contains(&row))
+            .map(|span| span.rows.clone())
+            .collect()
+    }
 
-        let kind = node.kind();
+    /// A tree-sitter range for the bytes `start..end` of the source.
+    fn byte_range(&self, start: usize, end: usize) -> ts::Range {
+        let point = |byte: usize| {
+            let row = self.source.byte_to_line(byte);
+            ts::Point::new(row, byte - self.source.line_to_byte(row))
+        };
+        ts::Range {
+            start_byte: start,
+            end_byte: end,
+            start_point: point(start),
+            end_point: point(end),
+        }
+    }
+}
 
-        if node.child_count() == 0 || kind == "line_comment" || kind == "block_comment" {
-            let style = self.resolve_semantic_style(node);
+/// Where byte `byte`, which lies after `edit`, ends up once it is applied.
+fn shift_byte(byte: usize, edit: &ts::InputEdit) -> usize {
+    byte + edit.new_end_byte - edit.old_end_byte
+}
 
-            if node.start_position().row == line_idx {
-                let start_col = node.start_position().column;
-                let end_col = if node.end_position().row == line_idx {
-                    node.end_position().column
-                } else {
-                    usize::MAX
-                };
-                spans.push((start_col, end_col, style));
-            } else if node.end_position().row == line_idx {
-                spans.push((0, node.end_position().column, style));
-            } else {
-                spans.push((0, usize::MAX, style));
-            }
+/// `range`, which lies after `edit`, moved to where it is once the edit is
+/// applied.
+fn shift_range(range: &ts::Range, edit: &ts::InputEdit) -> ts::Range {
+    let point = |point: ts::Point| {
+        if point.row == edit.old_end_position.row {
+            ts::Point::new(
+                edit.new_end_position.row,
+                point.column + edit.new_end_position.column - edit.old_end_position.column,
+            )
         } else {
-            let mut cursor = node.walk();
-            for child in node.children(&mut cursor) {
-                self.collect_leaf_styles(child, line_idx, spans);
-            }
+            ts::Point::new(
+                point.row + edit.new_end_position.row - edit.old_end_position.row,
+                point.column,
+            )
         }
+    };
+    ts::Range {
+        start_byte: shift_byte(range.start_byte, edit),
+        end_byte: shift_byte(range.end_byte, edit),
+        start_point: point(range.start_point),
+        end_point: point(range.end_point),
     }
 }
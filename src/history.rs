@@ -0,0 +1,292 @@
+use std::path::{Path, PathBuf};
+
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, diagnostic::find_project_dir};
+
+/// Oldest transactions beyond this count are dropped when persisting.
+const MAX_PERSISTED: usize = 1000;
+
+/// A single replacement in the rope: `removed` was replaced by `inserted`
+/// starting at char index `pos`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Edit {
+    pub pos: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    pub fn inverse(&self) -> Self {
+        Self {
+            pos: self.pos,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+/// A group of edits undone and redone as one step, with the cursor
+/// positions `(x, y)` to restore on either side.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub edits: Vec<Edit>,
+    pub cursor_before: (usize, usize),
+    pub cursor_after: (usize, usize),
+    kind: EditKind,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    /// Whether the last transaction still accepts edits.
+    #[serde(skip)]
+    open: bool,
+    /// Depth of explicit groups (see `begin_group`).
+    #[serde(skip)]
+    grouping: usize,
+    /// Length of `undo` when the file was last saved, or `None` once the
+    /// saved state was dropped from the redo stack.
+    #[serde(skip)]
+    saved: Option<usize>,
+}
+
+impl History {
+    /// Record an applied edit. Consecutive edits of the same kind are merged
+    /// as long as the cursor didn't move in between, so a run of typed chars
+    /// undoes as a single step.
+    pub fn record(
+        &mut self,
+        edit: Edit,
+        kind: EditKind,
+        cursor_before: (usize, usize),
+        cursor_after: (usize, usize),
+    ) {
+        self.redo.clear();
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+
+        if self.open
+            && let Some(last) = self.undo.last_mut()
+            && (self.grouping > 0
+                || (kind != EditKind::Other
+                    && last.kind == kind
+                    && last.cursor_after == cursor_before))
+        {
+            last.edits.push(edit);
+            last.cursor_after = cursor_after;
+            return;
+        }
+
+        self.undo.push(Transaction {
+            edits: vec![edit],
+            cursor_before,
+            cursor_after,
+            kind,
+        });
+        self.open = true;
+    }
+
+    /// Close the current transaction so the next edit starts a new one.
+    pub fn seal(&mut self) {
+        self.open = false;
+    }
+
+    /// Merge every edit recorded until the matching `end_group` into a
+    /// single transaction.
+    pub fn begin_group(&mut self) {
+        if self.grouping == 0 {
+            self.seal();
+        }
+        self.grouping += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        self.grouping = self.grouping.saturating_sub(1);
+        if self.grouping == 0 {
+            self.seal();
+        }
+    }
+
+    /// Overwrite where redoing the last transaction leaves the cursor, for
+    /// edits whose final cursor is only known once they are all applied.
+    pub fn set_cursor_after(&mut self, cursor: (usize, usize)) {
+        if let Some(last) = self.undo.last_mut() {
+            last.cursor_after = cursor;
+        }
+    }
+
+    /// Remember the current position as matching the file on disk. The
+    /// last transaction is sealed so later edits don't merge into it.
+    pub fn mark_saved(&mut self) {
+        self.seal();
+        self.saved = Some(self.undo.len());
+    }
+
+    /// Whether undo/redo led back to the state of the last save.
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo.len())
+    }
+
+    pub fn undo(&mut self) -> Option<Transaction> {
+        self.seal();
+        let tx = self.undo.pop()?;
+        self.redo.push(tx.clone());
+        Some(tx)
+    }
+
+    pub fn redo(&mut self) -> Option<Transaction> {
+        self.seal();
+        let tx = self.redo.pop()?;
+        self.undo.push(tx.clone());
+        Some(tx)
+    }
+}
+
+/// On-disk form of a history, tied to the file contents it applies to.
+#[derive(Serialize, Deserialize)]
+struct UndoFile {
+    path: PathBuf,
+    content_hash: u64,
+    history: History,
+}
+
+impl History {
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty() && self.redo.is_empty()
+    }
+
+    /// Write the history for `file` to the undo cache. `content` is the
+    /// buffer text; when it has unsaved edits, only the history up to the
+    /// last save is written, since that is what matches the file on disk.
+    pub fn persist(&self, file: &Path, content: &Rope) -> anyhow::Result<()> {
+        let file = canonical(file);
+        let Some(cache) = undo_cache_path(&file) else {
+            return Ok(());
+        };
+        // The saved state was overwritten by new edits, so nothing in the
+        // history leads back to the file on disk any more.
+        let Some((history, content)) = self.at_saved(content) else {
+            return Ok(());
+        };
+        if history.is_empty() {
+            if cache.exists() {
+                std::fs::remove_file(&cache)?;
+            }
+            return Ok(());
+        }
+
+        let skip = history.undo.len().saturating_sub(MAX_PERSISTED);
+        let undo_file = UndoFile {
+            path: file.clone(),
+            content_hash: hash_rope(&content),
+            history: History {
+                undo: history.undo[skip..].to_vec(),
+                redo: history.redo,
+                ..History::default()
+            },
+        };
+
+        if let Some(dir) = cache.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&cache, serde_json::to_string(&undo_file)?)?;
+        Ok(())
+    }
+
+    /// The undo and redo stacks as of the last save, with the text they
+    /// apply to: `content` with every transaction since the save undone or
+    /// redone.
+    fn at_saved(&self, content: &Rope) -> Option<(History, Rope)> {
+        let saved = self.saved?;
+        let mut text = content.clone();
+        let mut undo = self.undo.clone();
+        let mut redo = self.redo.clone();
+        while undo.len() > saved {
+            let tx = undo.pop()?;
+            for edit in tx.edits.iter().rev() {
+                apply(&mut text, &edit.inverse());
+            }
+            redo.push(tx);
+        }
+        while undo.len() < saved {
+            let tx = redo.pop()?;
+            for edit in &tx.edits {
+                apply(&mut text, edit);
+            }
+            undo.push(tx);
+        }
+        let history = History {
+            undo,
+            redo,
+            ..History::default()
+        };
+        Some((history, text))
+    }
+
+    /// Load the persisted history for `file`, provided it was saved against
+    /// the same `content`.
+    pub fn restore(file: &Path, content: &Rope) -> Option<Self> {
+        let file = canonical(file);
+        let raw = std::fs::read_to_string(undo_cache_path(&file)?).ok()?;
+        let undo_file: UndoFile = serde_json::from_str(&raw).ok()?;
+        if undo_file.path != file || undo_file.content_hash != hash_rope(content) {
+            return None;
+        }
+        Some(undo_file.history)
+    }
+}
+
+/// `file` with symlinks and `..` resolved, so every way of opening a file
+/// shares one cache entry.
+fn canonical(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+}
+
+fn apply(text: &mut Rope, edit: &Edit) {
+    text.remove(edit.pos..edit.pos + edit.removed.chars().count());
+    text.insert(edit.pos, &edit.inserted);
+}
+
+/// `<state dir>/undo/<hash of project>/<hash of path>.json`, the project
+/// being the nearest Cargo root or the file's directory. The cache lives
+/// outside the project so it never shows up in its working tree.
+fn undo_cache_path(file: &Path) -> Option<PathBuf> {
+    let project = find_project_dir(file)
+        .or_else(|| file.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let project_key = fnv1a(FNV_OFFSET, project.to_string_lossy().as_bytes());
+    let key = fnv1a(FNV_OFFSET, file.to_string_lossy().as_bytes());
+    Some(
+        config::state_dir()?
+            .join("undo")
+            .join(format!("{:016x}", project_key))
+            .join(format!("{:016x}.json", key)),
+    )
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// FNV-1a, used instead of `DefaultHasher` so keys stay stable across builds.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn hash_rope(text: &Rope) -> u64 {
+    text.chunks()
+        .fold(FNV_OFFSET, |hash, chunk| fnv1a(hash, chunk.as_bytes()))
+}
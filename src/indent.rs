@@ -0,0 +1,412 @@
+use ropey::Rope;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    ops::Range,
+    path::Path,
+};
+use tree_sitter::{self as ts, StreamingIterator};
+
+use crate::{editorconfig, highlighter::Highlighter};
+
+/// Lines looked at to guess how a file is indented.
+const DETECT_LINES: usize = 1000;
+
+/// How a buffer is indented.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndentStyle {
+    /// Indent with tabs rather than spaces.
+    pub tabs: bool,
+    /// Columns per indent level.
+    pub width: usize,
+    /// Columns between tab stops.
+    pub tab_width: usize,
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        Self {
+            tabs: false,
+            width: 4,
+            tab_width: 4,
+        }
+    }
+}
+
+impl IndentStyle {
+    /// Style for the file at `path`: what its `.editorconfig` files say, or
+    /// else what its contents use, or else 4 spaces.
+    pub fn for_file(path: &Path, text: &Rope) -> Self {
+        let mut style = Self::detect(text).unwrap_or_default();
+        let properties = editorconfig::properties(path);
+        let number = |key: &str| {
+            properties
+                .get(key)
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|&n| n > 0)
+        };
+
+        match properties.get("indent_style").map(String::as_str) {
+            Some("tab") => style.tabs = true,
+            Some("space") => style.tabs = false,
+            _ => {}
+        }
+        if let Some(tab_width) = number("tab_width") {
+            style.tab_width = tab_width;
+        }
+        match properties.get("indent_size").map(String::as_str) {
+            Some("tab") => style.width = style.tab_width,
+            _ => {
+                if let Some(width) = number("indent_size") {
+                    style.width = width;
+                    if number("tab_width").is_none() {
+                        style.tab_width = width;
+                    }
+                } else if properties.get("indent_style").is_some_and(|s| s == "tab") {
+                    style.width = style.tab_width;
+                }
+            }
+        }
+        style
+    }
+
+    /// Guess the style from the leading whitespace of the first lines: tabs
+    /// if more lines start with one than with spaces, or else the most
+    /// common step between the indents of consecutive lines.
+    pub fn detect(text: &Rope) -> Option<Self> {
+        let (mut tab_lines, mut space_lines) = (0, 0);
+        let mut steps = [0usize; 9];
+        let mut previous = 0;
+        for line in text.lines().take(DETECT_LINES) {
+            if line.chars().all(char::is_whitespace) {
+                continue;
+            }
+            if line.chars().next() == Some('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let spaces = line.chars().take_while(|&c| c == ' ').count();
+            if spaces > 0 {
+                space_lines += 1;
+            }
+            let step = spaces.abs_diff(previous);
+            if (2..steps.len()).contains(&step) {
+                steps[step] += 1;
+            }
+            previous = spaces;
+        }
+
+        if tab_lines > space_lines {
+            let tab_width = Self::default().tab_width;
+            return Some(Self {
+                tabs: true,
+                width: tab_width,
+                tab_width,
+            });
+        }
+        let (width, &count) = steps.iter().enumerate().rev().max_by_key(|&(_, n)| n)?;
+        (count > 0).then_some(Self {
+            tabs: false,
+            width,
+            tab_width: Self::default().tab_width,
+        })
+    }
+
+    /// Whitespace indenting a line by `columns`.
+    pub fn whitespace(&self, columns: usize) -> String {
+        if self.tabs {
+            let tabs = columns / self.tab_width;
+            "\t".repeat(tabs) + &" ".repeat(columns - tabs * self.tab_width)
+        } else {
+            " ".repeat(columns)
+        }
+    }
+}
+
+impl Display for IndentStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.tabs {
+            write!(f, "tabs: {}", self.tab_width)
+        } else {
+            write!(f, "spaces: {}", self.width)
+        }
+    }
+}
+
+/// Where the `indents` query puts a line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Indent {
+    /// One level deeper than the given earlier line.
+    Below(usize),
+    /// At the same level as the given earlier line.
+    Like(usize),
+    /// At the start of the line.
+    Top,
+    /// Leave the line as it is, e.g. inside a multi-line string.
+    Keep,
+}
+
+/// Indent of a line starting at byte `start` whose text begins at byte
+/// `first`, after any leading whitespace. `start` may also be a position
+/// about to be split by a line break, as the tree doesn't have to know about
+/// the line yet. None when the language has no indent rules.
+///
+/// The line goes one level below the line on which its innermost `@indent`
+/// node starts, counting only nodes starting before `start`. A line opening
+/// with an `@outdent` node lines up with the line its parent starts on. Code
+/// that doesn't parse, such as a function being typed, is indented by its
+/// unclosed brackets instead.
+pub fn compute(
+    highlighter: &Highlighter,
+    text: &Rope,
+    start: usize,
+    first: usize,
+) -> Option<Indent> {
+    let (Some(tree), Some(query)) = (
+        highlighter.tree(),
+        highlighter
+            .language()
+            .and_then(|lang| lang.indents.as_ref()),
+    ) else {
+        return None;
+    };
+    let rules = Rules::collect(query, tree, text, start.saturating_sub(1)..first + 1);
+
+    let end = (first + 1).min(text.len_bytes());
+    let Some(deepest) = tree.root_node().descendant_for_byte_range(first, end) else {
+        return Some(Indent::Top);
+    };
+    let ancestors: Vec<ts::Node> =
+        std::iter::successors(Some(deepest), |node| node.parent()).collect();
+    let enclosing: Vec<ts::Node> = ancestors
+        .iter()
+        .copied()
+        .filter(|node| node.start_byte() < start && node.end_byte() > first)
+        .collect();
+    if enclosing.iter().any(|&node| rules.is("ignore", node)) {
+        return Some(Indent::Keep);
+    }
+
+    if let Some(item) = enclosing_item(tree.root_node(), start)
+        && item.has_error()
+    {
+        let closing = matches!(text.get_byte(first), Some(b'}' | b')' | b']'));
+        return Some(by_brackets(item, start, closing));
+    }
+
+    let mut opening = ancestors
+        .iter()
+        .take_while(|node| node.start_byte() == first);
+    if opening.any(|&node| rules.is("outdent", node)) {
+        return Some(enclosing.first().map_or(Indent::Top, |parent| {
+            Indent::Like(parent.start_position().row)
+        }));
+    }
+
+    let Some(&node) = enclosing.iter().find(|&&node| rules.is("indent", node)) else {
+        return Some(Indent::Top);
+    };
+    Some(match rules.anchors.get(&node.id()) {
+        Some(anchor) if anchor.end_position().row > anchor.start_position().row => {
+            Indent::Like(anchor.end_position().row)
+        }
+        _ => Indent::Below(node.start_position().row),
+    })
+}
+
+/// Captures of the `indents` query around a line.
+struct Rules<'t> {
+    /// `(capture name, node id)` pairs.
+    captured: HashSet<(String, usize)>,
+    /// `@indent.anchor` nodes by the id of their `@indent` node.
+    anchors: HashMap<usize, ts::Node<'t>>,
+}
+
+impl<'t> Rules<'t> {
+    /// Captures of the matches overlapping `range`.
+    fn collect(query: &ts::Query, tree: &'t ts::Tree, text: &Rope, range: Range<usize>) -> Self {
+        let mut rules = Rules {
+            captured: HashSet::new(),
+            anchors: HashMap::new(),
+        };
+        let mut cursor = ts::QueryCursor::new();
+        cursor.set_byte_range(range);
+        let mut matches = cursor.matches(query, tree.root_node(), |node: ts::Node| {
+            text.byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        });
+        while let Some(m) = matches.next() {
+            let mut indent = None;
+            let mut anchor = None;
+            for capture in m.captures {
+                let name = query.capture_names()[capture.index as usize];
+                match name {
+                    "indent" => indent = Some(capture.node.id()),
+                    "indent.anchor" => anchor = Some(capture.node),
+                    _ => {}
+                }
+                rules.captured.insert((name.to_string(), capture.node.id()));
+            }
+            if let (Some(indent), Some(anchor)) = (indent, anchor) {
+                rules.anchors.insert(indent, anchor);
+            }
+        }
+        rules
+    }
+
+    fn is(&self, name: &str, node: ts::Node) -> bool {
+        self.captured.contains(&(name.to_string(), node.id()))
+    }
+}
+
+/// Top-level node containing or preceding byte `start`, or the whole tree
+/// when it failed to parse from the start.
+fn enclosing_item(root: ts::Node, start: usize) -> Option<ts::Node> {
+    if root.is_error() {
+        return Some(root);
+    }
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .take_while(|child| child.start_byte() < start)
+        .last()
+}
+
+/// Indent from the brackets of `node` left open before byte `start`: one
+/// level below the line of the innermost one, or on that line for a line
+/// opening with a `closing` bracket.
+fn by_brackets(node: ts::Node, start: usize, closing: bool) -> Indent {
+    let mut open = Vec::new();
+    let mut cursor = node.walk();
+    'walk: loop {
+        let leaf = cursor.node();
+        if leaf.start_byte() >= start {
+            break;
+        }
+        if leaf.child_count() == 0 && !leaf.is_missing() {
+            match leaf.kind() {
+                "{" | "(" | "[" => open.push(leaf.start_position().row),
+                "}" | ")" | "]" => {
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    match open.last() {
+        Some(&row) if closing => Indent::Like(row),
+        Some(&row) => Indent::Below(row),
+        None => Indent::Top,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{language, theme::Theme};
+
+    fn detect(text: &str) -> Option<IndentStyle> {
+        IndentStyle::detect(&Rope::from_str(text))
+    }
+
+    #[test]
+    fn detects_space_width() {
+        let two = "fn a() {\n  if x {\n    y();\n  }\n}\n";
+        assert_eq!(detect(two).map(|s| (s.tabs, s.width)), Some((false, 2)));
+        let four = "fn a() {\n    if x {\n        y();\n    }\n}\n";
+        assert_eq!(detect(four).map(|s| (s.tabs, s.width)), Some((false, 4)));
+    }
+
+    #[test]
+    fn ignores_odd_alignment() {
+        // Continuation lines aligned to a paren don't outvote the real steps.
+        let text = "fn a() {\n    call(x,\n         y);\n    if z {\n        w();\n    }\n}\n";
+        assert_eq!(detect(text).map(|s| s.width), Some(4));
+    }
+
+    #[test]
+    fn detects_tabs() {
+        let text = "fn a() {\n\tif x {\n\t\ty();\n\t}\n}\n";
+        assert_eq!(
+            detect(text),
+            Some(IndentStyle {
+                tabs: true,
+                width: 4,
+                tab_width: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn nothing_to_detect() {
+        assert_eq!(detect("a\nb\n\n   \nc\n"), None);
+        assert_eq!(detect(""), None);
+    }
+
+    #[test]
+    fn whitespace_for_columns() {
+        let tabs = IndentStyle {
+            tabs: true,
+            width: 4,
+            tab_width: 4,
+        };
+        assert_eq!(tabs.whitespace(10), "\t\t  ");
+        assert_eq!(IndentStyle::default().whitespace(3), "   ");
+    }
+
+    /// What the `indents` query says about `line` of the Rust `source`.
+    fn rule(source: &str, line: usize) -> Option<Indent> {
+        let text = Rope::from_str(source);
+        let rust = language::by_name("rust");
+        let mut highlighter = Highlighter::new(rust, &Theme::vscode_dark_modern());
+        highlighter.update(&text);
+        let start = text.line_to_byte(line);
+        let leading = text
+            .line(line)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .count();
+        compute(&highlighter, &text, start, start + leading)
+    }
+
+    #[test]
+    fn block_contents_and_closing_brackets() {
+        let source = "fn f() {\n    match x {\n        1 => {}\n    }\n}\n";
+        assert_eq!(rule(source, 0), Some(Indent::Top));
+        assert_eq!(rule(source, 1), Some(Indent::Below(0)));
+        assert_eq!(rule(source, 2), Some(Indent::Below(1)));
+        assert_eq!(rule(source, 3), Some(Indent::Like(1)));
+        assert_eq!(rule(source, 4), Some(Indent::Like(0)));
+    }
+
+    #[test]
+    fn arguments_and_method_chains() {
+        let call = "fn f() {\n    call(a,\n    b);\n}\n";
+        assert_eq!(rule(call, 2), Some(Indent::Below(1)));
+        let chain = "fn f() {\n    foo\n        .bar()\n        .baz();\n}\n";
+        assert_eq!(rule(chain, 3), Some(Indent::Like(2)));
+    }
+
+    #[test]
+    fn strings_are_kept() {
+        let source = "fn f() {\n    let s = \"a\nb\";\n}\n";
+        assert_eq!(rule(source, 2), Some(Indent::Keep));
+    }
+
+    #[test]
+    fn unfinished_code_goes_by_brackets() {
+        assert_eq!(rule("fn f() {\n    if x {\n", 2), Some(Indent::Below(1)));
+        assert_eq!(
+            rule("fn f() {\n    let x = (1,\n", 2),
+            Some(Indent::Below(1))
+        );
+    }
+}
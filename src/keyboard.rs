@@ -1,7 +1,10 @@
 use anyhow::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-use crate::{editor::Editor, mode::EditorMode};
+use crate::{
+    editor::Editor, fold::FoldCommand, mode::EditorMode, project_search::SearchFocus,
+    substitute::Substitute, textobject,
+};
 
 const DEFAULT_SCROLL_JUMP: usize = 10;
 
@@ -36,7 +39,20 @@ impl KeyboardHandler {
         match editor.mode.clone() {
             EditorMode::Nav => self.handle_nav(key, editor),
             EditorMode::Insert => self.handle_insert(key, editor),
+            EditorMode::Visual | EditorMode::VisualLine => self.handle_visual(key, editor),
             EditorMode::TreeNav => self.handle_tree(key, editor),
+            EditorMode::Search => Self::handle_search(key, editor),
+            EditorMode::ProjectSearch => Self::handle_project_search(key, editor),
+            EditorMode::Finder => Self::handle_finder(key, editor),
+            EditorMode::Outline => Self::handle_outline(key, editor),
+            EditorMode::Confirm => {
+                match key.code {
+                    KeyCode::Char(c) => editor.answer_confirm(c),
+                    KeyCode::Esc => editor.answer_confirm('q'),
+                    _ => {}
+                }
+                Ok(())
+            }
             EditorMode::Command {
                 mut command_str,
                 former_mode,
@@ -76,6 +92,11 @@ impl KeyboardHandler {
     }
 
     fn handle_nav(&self, key: KeyEvent, editor: &mut Editor) -> Result<()> {
+        if !editor.pending_keys.is_empty() {
+            Self::handle_pending(key, editor);
+            return Ok(());
+        }
+
         match key.code {
             KeyCode::Char('x') if key.modifiers == KeyModifiers::CONTROL => {
                 editor.show_tree = true;
@@ -84,20 +105,69 @@ impl KeyboardHandler {
             KeyCode::Char('n') if key.modifiers == KeyModifiers::CONTROL => {
                 editor.next_buffer();
             }
-            KeyCode::Char('p') if key.modifiers == KeyModifiers::CONTROL => {
+            KeyCode::Char('b') if key.modifiers == KeyModifiers::CONTROL => {
                 editor.prev_buffer();
             }
+            KeyCode::Char('p') if key.modifiers == KeyModifiers::CONTROL => {
+                editor.open_finder();
+            }
             KeyCode::Char('w') if key.modifiers == KeyModifiers::CONTROL => {
                 if let Some(i) = editor.active_buffer {
                     editor.close_buffer(i);
                 }
             }
+            KeyCode::Char('r') if key.modifiers == KeyModifiers::CONTROL => {
+                editor.redo();
+            }
+            KeyCode::Char('f') if key.modifiers == KeyModifiers::CONTROL => {
+                editor.open_project_search();
+            }
+            KeyCode::Char('o') if key.modifiers == KeyModifiers::CONTROL => {
+                editor.toggle_outline();
+            }
+            KeyCode::Up if key.modifiers == KeyModifiers::ALT => editor.expand_selection(),
             KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right => {
                 self.handle_arrows(key, editor)?;
             }
             KeyCode::Char('i') => {
                 editor.mode = EditorMode::Insert;
             }
+            KeyCode::Char('u') => {
+                editor.undo();
+            }
+            KeyCode::Char('p') => {
+                editor.paste(false);
+            }
+            KeyCode::Char('P') => {
+                editor.paste(true);
+            }
+            KeyCode::Char('"') => {
+                editor.pending_keys.push('"');
+            }
+            KeyCode::Char(c @ ('z' | ']' | '[' | 'd' | 'y' | 'c')) => {
+                editor.pending_keys.push(c);
+            }
+            KeyCode::Char('/') => {
+                editor.start_search(true);
+            }
+            KeyCode::Char('?') => {
+                editor.start_search(false);
+            }
+            KeyCode::Char('n') => {
+                editor.search_next(false);
+            }
+            KeyCode::Char('N') => {
+                editor.search_next(true);
+            }
+            KeyCode::Esc => {
+                editor.search.clear();
+            }
+            KeyCode::Char('v') => {
+                editor.start_visual(EditorMode::Visual);
+            }
+            KeyCode::Char('V') => {
+                editor.start_visual(EditorMode::VisualLine);
+            }
             KeyCode::Char('$') => {
                 if let Some(buf) = editor.buf_mut() {
                     buf.jump_to_line_end();
@@ -111,6 +181,16 @@ impl KeyboardHandler {
             KeyCode::Char(':') => {
                 editor.mode = EditorMode::command(EditorMode::Nav);
             }
+            KeyCode::Tab | KeyCode::BackTab => {
+                if let Some(buf) = editor.buf_mut() {
+                    let line = buf.cursor_y;
+                    if key.code == KeyCode::Tab {
+                        buf.indent_lines(line, line);
+                    } else {
+                        buf.outdent_lines(line, line);
+                    }
+                }
+            }
             _ => {}
         }
         Ok(())
@@ -122,14 +202,106 @@ impl KeyboardHandler {
                 self.handle_arrows(key, editor)?;
             }
             KeyCode::Char(c) => editor.insert_char(c),
+            KeyCode::Tab => {
+                if let Some(buf) = editor.buf_mut() {
+                    buf.insert_tab();
+                }
+            }
+            KeyCode::BackTab => {
+                if let Some(buf) = editor.buf_mut() {
+                    buf.shift_line(true);
+                }
+            }
             KeyCode::Backspace => editor.delete_char(),
             KeyCode::Enter => editor.insert_newline(),
-            KeyCode::Esc => editor.mode = EditorMode::Nav,
+            KeyCode::Esc => {
+                if let Some(buf) = editor.buf_mut() {
+                    buf.history.seal();
+                }
+                editor.mode = EditorMode::Nav;
+            }
             _ => {}
         }
         Ok(())
     }
 
+    fn handle_visual(&self, key: KeyEvent, editor: &mut Editor) -> Result<()> {
+        if !editor.pending_keys.is_empty() {
+            Self::handle_pending(key, editor);
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Up if key.modifiers == KeyModifiers::ALT => editor.expand_selection(),
+            KeyCode::Down if key.modifiers == KeyModifiers::ALT => editor.shrink_selection(),
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right => {
+                self.handle_arrows(key, editor)?;
+            }
+            KeyCode::Char('$') => {
+                if let Some(buf) = editor.buf_mut() {
+                    buf.jump_to_line_end();
+                }
+            }
+            KeyCode::Char('*') => {
+                if let Some(buf) = editor.buf_mut() {
+                    buf.jump_to_line_indent();
+                }
+            }
+            KeyCode::Char('v') if editor.mode == EditorMode::Visual => editor.exit_visual(),
+            KeyCode::Char('V') if editor.mode == EditorMode::VisualLine => editor.exit_visual(),
+            KeyCode::Char('v') => editor.mode = EditorMode::Visual,
+            KeyCode::Char('V') => editor.mode = EditorMode::VisualLine,
+            KeyCode::Char('y') => editor.yank_selection(),
+            KeyCode::Char('d') | KeyCode::Char('x') => editor.delete_selection(),
+            KeyCode::Char('c') => editor.change_selection(),
+            KeyCode::Char('>') => editor.indent_selection(false),
+            KeyCode::Char('<') => editor.indent_selection(true),
+            KeyCode::Char('=') => editor.reindent_selection(),
+            KeyCode::Tab => editor.indent_selection(false),
+            KeyCode::BackTab => editor.indent_selection(true),
+            KeyCode::Char(c @ ('"' | 'i' | 'a' | ']' | '[')) => editor.pending_keys.push(c),
+            KeyCode::Esc => editor.exit_visual(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Complete a multi-key command started in Nav or Visual mode. Any key
+    /// that doesn't continue the sequence cancels it.
+    fn handle_pending(key: KeyEvent, editor: &mut Editor) {
+        let pending = std::mem::take(&mut editor.pending_keys);
+        match (pending.as_str(), key.code) {
+            ("\"", KeyCode::Char(c)) if c.is_ascii_alphanumeric() || c == '"' => {
+                editor.register_name = Some(c);
+            }
+            ("z", KeyCode::Char(c)) => {
+                if let Some(command) = FoldCommand::from_key(c) {
+                    editor.fold(command);
+                }
+            }
+            ("]" | "[", KeyCode::Char(c)) => {
+                if let Some(object) = textobject::motion_for_key(c) {
+                    editor.goto_object(object, pending == "]");
+                }
+            }
+            ("i" | "a", KeyCode::Char(c)) => {
+                if let Some(object) = textobject::object_for_key(c) {
+                    editor.select_object(object, pending == "i");
+                }
+            }
+            ("d" | "y" | "c", KeyCode::Char(scope @ ('i' | 'a'))) => {
+                editor.pending_keys = format!("{}{}", pending, scope);
+            }
+            ("di" | "da" | "yi" | "ya" | "ci" | "ca", KeyCode::Char(c)) => {
+                if let Some(object) = textobject::object_for_key(c) {
+                    let operator = pending.chars().next().unwrap_or_default();
+                    editor.operate_on_object(operator, object, pending.ends_with('i'));
+                }
+            }
+            _ => {}
+        }
+    }
+
     fn handle_tree(&self, key: KeyEvent, editor: &mut Editor) -> Result<()> {
         match key.code {
             KeyCode::Up => editor.file_tree.move_up(),
@@ -151,11 +323,133 @@ impl KeyboardHandler {
             KeyCode::Char(':') => {
                 editor.mode = EditorMode::command(EditorMode::TreeNav);
             }
+            KeyCode::Char('f') if key.modifiers == KeyModifiers::CONTROL => {
+                editor.open_project_search();
+            }
+            KeyCode::Char('p') if key.modifiers == KeyModifiers::CONTROL => {
+                editor.open_finder();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_search(key: KeyEvent, editor: &mut Editor) -> Result<()> {
+        match key.code {
+            KeyCode::Char(c) => {
+                editor.search.query.push(c);
+                editor.update_search();
+            }
+            KeyCode::Backspace if editor.search.query.pop().is_none() => {
+                editor.cancel_search();
+            }
+            KeyCode::Backspace => editor.update_search(),
+            KeyCode::Enter => {
+                editor.mode = EditorMode::Nav;
+                if editor.search.matches.is_empty() && !editor.search.query.is_empty() {
+                    editor.message = Some(format!("pattern not found: {}", editor.search.query));
+                }
+            }
+            KeyCode::Esc => editor.cancel_search(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_finder(key: KeyEvent, editor: &mut Editor) -> Result<()> {
+        let Some(finder) = editor.finder.as_mut() else {
+            editor.close_finder();
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Char(c) => {
+                finder.query.push(c);
+                finder.update();
+            }
+            KeyCode::Backspace => {
+                finder.query.pop();
+                finder.update();
+            }
+            KeyCode::Up => finder.move_up(),
+            KeyCode::Down => finder.move_down(),
+            KeyCode::Enter => editor.open_finder_selection()?,
+            KeyCode::Esc => editor.close_finder(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_outline(key: KeyEvent, editor: &mut Editor) -> Result<()> {
+        let outline = &mut editor.outline;
+        match key.code {
+            KeyCode::Char('o') if key.modifiers == KeyModifiers::CONTROL => {
+                editor.toggle_outline();
+            }
+            KeyCode::Char(c) => {
+                outline.filter.push(c);
+                outline.selected = 0;
+            }
+            KeyCode::Backspace => {
+                outline.filter.pop();
+                outline.selected = 0;
+            }
+            KeyCode::Up => outline.move_up(),
+            KeyCode::Down => outline.move_down(),
+            KeyCode::Enter => editor.open_outline_symbol(),
+            KeyCode::Esc => editor.close_outline(),
             _ => {}
         }
         Ok(())
     }
 
+    fn handle_project_search(key: KeyEvent, editor: &mut Editor) -> Result<()> {
+        let search = &mut editor.project_search;
+        match search.focus {
+            SearchFocus::Query => match key.code {
+                KeyCode::Char(c) => search.query.push(c),
+                KeyCode::Backspace => {
+                    search.query.pop();
+                }
+                KeyCode::Enter if !search.query.is_empty() => search.run(),
+                KeyCode::Down => search.focus = SearchFocus::Hits,
+                KeyCode::Esc => editor.close_project_search(),
+                _ => {}
+            },
+            SearchFocus::Hits => match key.code {
+                KeyCode::Up if search.selected == 0 => search.focus = SearchFocus::Query,
+                KeyCode::Up => search.move_up(),
+                KeyCode::Down => search.move_down(),
+                KeyCode::Enter => editor.open_search_hit()?,
+                KeyCode::Char('/') | KeyCode::Char('i') => search.focus = SearchFocus::Query,
+                KeyCode::Char('r') => search.focus = SearchFocus::Replacement,
+                KeyCode::Esc => editor.close_project_search(),
+                _ => {}
+            },
+            SearchFocus::Replacement => match key.code {
+                KeyCode::Char(c) => search.replacement.push(c),
+                KeyCode::Backspace => {
+                    search.replacement.pop();
+                }
+                KeyCode::Enter => search.start_preview(),
+                KeyCode::Esc => search.focus = SearchFocus::Hits,
+                _ => {}
+            },
+            SearchFocus::Preview => match key.code {
+                KeyCode::Up => search.move_up(),
+                KeyCode::Down => search.move_down(),
+                KeyCode::Char(' ') => search.toggle_selected(),
+                KeyCode::Enter => {
+                    if let Err(e) = editor.apply_project_replace() {
+                        editor.message = Some(format!("replace failed: {}", e));
+                    }
+                }
+                KeyCode::Esc => search.focus = SearchFocus::Replacement,
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+
     fn handle_command(
         key: KeyEvent,
         editor: &mut Editor,
@@ -165,11 +459,9 @@ impl KeyboardHandler {
         match key.code {
             KeyCode::Char(':') => command_str.clear(),
             KeyCode::Char(c) => command_str.push(c),
-            KeyCode::Backspace => {
-                if command_str.pop().is_none() {
-                    editor.mode = former_mode.clone();
-                    return Ok(());
-                }
+            KeyCode::Backspace if command_str.pop().is_none() => {
+                editor.mode = former_mode.clone();
+                return Ok(());
             }
             KeyCode::Esc => {
                 editor.mode = former_mode.clone();
@@ -226,11 +518,61 @@ impl KeyboardHandler {
                 editor.next_buffer();
                 editor.mode = former_mode.clone();
             }
+            "find" | "files" => editor.open_finder(),
+            "outline" => editor.toggle_outline(),
             "bp" | "prev" => {
                 editor.prev_buffer();
                 editor.mode = former_mode.clone();
             }
+            "undo" => {
+                editor.undo();
+                editor.mode = former_mode.clone();
+            }
+            "redo" => {
+                editor.redo();
+                editor.mode = former_mode.clone();
+            }
+            other if let Some(command) = FoldCommand::from_command(other) => {
+                editor.fold(command);
+                editor.mode = former_mode.clone();
+            }
+            other if let Some((top, bottom)) = editor.reindent_range(other) => {
+                editor.reindent_lines(top, bottom);
+                editor.mode = former_mode.clone();
+            }
+            other if other.starts_with("grep ") => {
+                editor.open_project_search();
+                editor.project_search.query = other["grep ".len()..].to_string();
+                editor.project_search.run();
+            }
+            other if other.starts_with("set ") => {
+                editor.set_option(other["set ".len()..].trim());
+                editor.mode = former_mode.clone();
+            }
+            other if other == "theme" || other.starts_with("theme ") => {
+                editor.set_theme(other["theme".len()..].trim());
+                editor.mode = former_mode.clone();
+            }
             other => {
+                if let Some(buf) = editor.buf() {
+                    let last_line = buf.text.len_lines() - 1;
+                    let parsed =
+                        Substitute::parse(other, buf.cursor_y, last_line, &editor.search.query);
+                    match parsed {
+                        Some(Ok(sub)) => {
+                            editor.mode = former_mode.clone();
+                            editor.substitute(sub);
+                            return Ok(());
+                        }
+                        Some(Err(e)) => {
+                            editor.message = Some(e.to_string());
+                            editor.mode = former_mode.clone();
+                            return Ok(());
+                        }
+                        None => {}
+                    }
+                }
+
                 if let Ok(line) = other.parse::<usize>()
                     && let Some(buf) = editor.buf_mut()
                 {
@@ -0,0 +1,262 @@
+use regex::Regex;
+use ropey::Rope;
+use std::{path::Path, sync::OnceLock};
+use tree_sitter as ts;
+
+use crate::config;
+
+/// A language the editor can parse and highlight.
+pub struct Language {
+    /// Short identifier, also the directory name of its queries.
+    pub id: &'static str,
+    /// Human readable name shown in the status bar.
+    pub name: &'static str,
+    pub grammar: ts::Language,
+    pub highlights: ts::Query,
+    /// Regions of this language to parse with another one.
+    pub injections: Option<ts::Query>,
+    /// Regions that can be folded away.
+    pub folds: Option<ts::Query>,
+    /// Functions, arguments, strings and such, for motions and text objects.
+    pub textobjects: Option<ts::Query>,
+    /// Definitions listed in the outline panel.
+    pub outline: Option<ts::Query>,
+    /// Other constructs named in the breadcrumb, such as a `match`.
+    pub context: Option<ts::Query>,
+    /// Nodes whose contents are indented, used for auto-indent.
+    pub indents: Option<ts::Query>,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    interpreters: &'static [&'static str],
+    aliases: &'static [&'static str],
+}
+
+struct LanguageDef {
+    id: &'static str,
+    name: &'static str,
+    grammar: fn() -> ts::Language,
+    highlights: &'static str,
+    injections: Option<&'static str>,
+    folds: Option<&'static str>,
+    textobjects: Option<&'static str>,
+    outline: Option<&'static str>,
+    context: Option<&'static str>,
+    indents: Option<&'static str>,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    interpreters: &'static [&'static str],
+    aliases: &'static [&'static str],
+}
+
+const DEFS: &[LanguageDef] = &[
+    LanguageDef {
+        id: "rust",
+        name: "Rust",
+        grammar: || tree_sitter_rust::LANGUAGE.into(),
+        highlights: include_str!("../queries/rust/highlights.scm"),
+        injections: Some(include_str!("../queries/rust/injections.scm")),
+        folds: Some(include_str!("../queries/rust/folds.scm")),
+        textobjects: Some(include_str!("../queries/rust/textobjects.scm")),
+        outline: Some(include_str!("../queries/rust/outline.scm")),
+        context: Some(include_str!("../queries/rust/context.scm")),
+        indents: Some(include_str!("../queries/rust/indents.scm")),
+        extensions: &["rs"],
+        file_names: &[],
+        interpreters: &["rust-script", "cargo"],
+        aliases: &["rs"],
+    },
+    LanguageDef {
+        id: "toml",
+        name: "TOML",
+        grammar: || tree_sitter_toml_ng::LANGUAGE.into(),
+        highlights: include_str!("../queries/toml/highlights.scm"),
+        injections: None,
+        folds: Some(include_str!("../queries/toml/folds.scm")),
+        textobjects: None,
+        outline: Some(include_str!("../queries/toml/outline.scm")),
+        context: None,
+        indents: Some(include_str!("../queries/toml/indents.scm")),
+        extensions: &["toml"],
+        file_names: &["Cargo.lock"],
+        interpreters: &[],
+        aliases: &[],
+    },
+    LanguageDef {
+        id: "markdown",
+        name: "Markdown",
+        grammar: || tree_sitter_md::LANGUAGE.into(),
+        highlights: include_str!("../queries/markdown/highlights.scm"),
+        injections: Some(include_str!("../queries/markdown/injections.scm")),
+        folds: Some(include_str!("../queries/markdown/folds.scm")),
+        textobjects: None,
+        outline: Some(include_str!("../queries/markdown/outline.scm")),
+        context: None,
+        indents: None,
+        extensions: &["md", "markdown"],
+        file_names: &[],
+        interpreters: &[],
+        aliases: &["md"],
+    },
+    LanguageDef {
+        id: "json",
+        name: "JSON",
+        grammar: || tree_sitter_json::LANGUAGE.into(),
+        highlights: include_str!("../queries/json/highlights.scm"),
+        injections: None,
+        folds: Some(include_str!("../queries/json/folds.scm")),
+        textobjects: None,
+        outline: None,
+        context: None,
+        indents: Some(include_str!("../queries/json/indents.scm")),
+        extensions: &["json"],
+        file_names: &["flake.lock"],
+        interpreters: &[],
+        aliases: &[],
+    },
+    // Only reached through injections.
+    LanguageDef {
+        id: "markdown-inline",
+        name: "Markdown",
+        grammar: || tree_sitter_md::INLINE_LANGUAGE.into(),
+        highlights: include_str!("../queries/markdown-inline/highlights.scm"),
+        injections: None,
+        folds: None,
+        textobjects: None,
+        outline: None,
+        context: None,
+        indents: None,
+        extensions: &[],
+        file_names: &[],
+        interpreters: &[],
+        aliases: &[],
+    },
+    LanguageDef {
+        id: "regex",
+        name: "Regex",
+        grammar: || tree_sitter_regex::LANGUAGE.into(),
+        highlights: include_str!("../queries/regex/highlights.scm"),
+        injections: None,
+        folds: None,
+        textobjects: None,
+        outline: None,
+        context: None,
+        indents: None,
+        extensions: &[],
+        file_names: &[],
+        interpreters: &[],
+        aliases: &[],
+    },
+];
+
+/// Number of lines at each end of a file searched for a modeline.
+const MODELINE_LINES: usize = 5;
+
+/// All registered languages. Queries are compiled on first use, preferring
+/// `queries/<id>/<kind>.scm` from the user config directory over the bundled
+/// highlights, injections, folds, text objects, outline, context and indents.
+pub fn languages() -> &'static [Language] {
+    static LANGUAGES: OnceLock<Vec<Language>> = OnceLock::new();
+    LANGUAGES.get_or_init(|| DEFS.iter().map(load).collect())
+}
+
+fn load(def: &LanguageDef) -> Language {
+    let grammar = (def.grammar)();
+    let query = |kind: &str, bundled: &str| {
+        config::read_user_file(&format!("queries/{}/{}.scm", def.id, kind))
+            .and_then(|source| ts::Query::new(&grammar, &source).ok())
+            .unwrap_or_else(|| ts::Query::new(&grammar, bundled).expect("bundled query"))
+    };
+    let highlights = query("highlights", def.highlights);
+    let injections = def.injections.map(|bundled| query("injections", bundled));
+    let folds = def.folds.map(|bundled| query("folds", bundled));
+    let textobjects = def.textobjects.map(|bundled| query("textobjects", bundled));
+    let outline = def.outline.map(|bundled| query("outline", bundled));
+    let context = def.context.map(|bundled| query("context", bundled));
+    let indents = def.indents.map(|bundled| query("indents", bundled));
+    Language {
+        id: def.id,
+        name: def.name,
+        grammar,
+        highlights,
+        injections,
+        folds,
+        textobjects,
+        outline,
+        context,
+        indents,
+        extensions: def.extensions,
+        file_names: def.file_names,
+        interpreters: def.interpreters,
+        aliases: def.aliases,
+    }
+}
+
+/// Look a language up by id or alias, case-insensitively.
+pub fn by_name(name: &str) -> Option<&'static Language> {
+    languages().iter().find(|lang| {
+        lang.id.eq_ignore_ascii_case(name)
+            || lang.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    })
+}
+
+/// Pick the language for a file: a modeline wins, then the file name or
+/// extension, then the shebang interpreter. `None` means plain text.
+pub fn detect(path: &Path, text: &Rope) -> Option<&'static Language> {
+    if let Some(lang) = modeline(text) {
+        return Some(lang);
+    }
+
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if let Some(lang) = languages().iter().find(|lang| {
+        lang.file_names.contains(&file_name)
+            || lang
+                .extensions
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(extension))
+    }) {
+        return Some(lang);
+    }
+
+    let interpreter = shebang_interpreter(text)?;
+    languages()
+        .iter()
+        .find(|lang| lang.interpreters.contains(&interpreter.as_str()))
+}
+
+/// Language named by a vim (`vim: set ft=rust:`) or emacs (`-*- mode: rust -*-`)
+/// modeline near the start or end of the file.
+fn modeline(text: &Rope) -> Option<&'static Language> {
+    static PATTERNS: OnceLock<[Regex; 2]> = OnceLock::new();
+    let [vim, emacs] = PATTERNS.get_or_init(|| {
+        [
+            Regex::new(r"\b(?:vim?|ex):.*\b(?:ft|filetype)=([\w+-]+)").unwrap(),
+            Regex::new(r"-\*-\s*(?:.*\bmode:\s*)?([\w+-]+)\s*;?.*?-\*-").unwrap(),
+        ]
+    });
+
+    let count = text.len_lines();
+    let head = 0..count.min(MODELINE_LINES);
+    let tail = count.saturating_sub(MODELINE_LINES).max(head.end)..count;
+    head.chain(tail).find_map(|i| {
+        let line = text.line(i).to_string();
+        [vim, emacs]
+            .iter()
+            .find_map(|re| re.captures(&line))
+            .and_then(|caps| by_name(&caps[1]))
+    })
+}
+
+/// Program named on a `#!` first line, looking through `env` and its flags.
+fn shebang_interpreter(text: &Rope) -> Option<String> {
+    if text.len_lines() == 0 {
+        return None;
+    }
+    let first = text.line(0).to_string();
+    let command = first.strip_prefix("#!")?;
+    command
+        .split_whitespace()
+        .map(|word| word.rsplit('/').next().unwrap_or(word))
+        .find(|word| *word != "env" && !word.starts_with(['-', '+']))
+        .map(str::to_string)
+}
@@ -3,13 +3,29 @@ use anyhow::Result;
 use crate::editor::Editor;
 
 mod buffer;
+mod column;
+mod config;
+mod context;
 mod diagnostic;
 mod displayer;
 mod editor;
+mod editorconfig;
+mod finder;
+mod fold;
 mod highlighter;
+mod history;
+mod indent;
 mod keyboard;
+mod language;
 mod mode;
 mod mouse;
+mod outline;
+mod project_search;
+mod register;
+mod search;
+mod substitute;
+mod textobject;
+mod theme;
 mod tree;
 
 fn main() -> Result<()> {
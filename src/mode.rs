@@ -6,7 +6,15 @@ use ratatui::style::{Color, Style};
 pub enum EditorMode {
     Nav,
     Insert,
+    Visual,
+    VisualLine,
     TreeNav,
+    Finder,
+    ProjectSearch,
+    Outline,
+    Search,
+    /// Stepping through the matches of a `:s///c` command.
+    Confirm,
     Command {
         command_str: String,
         former_mode: Box<EditorMode>,
@@ -27,11 +35,21 @@ impl EditorMode {
         }
     }
 
+    pub fn is_visual(&self) -> bool {
+        matches!(self, Self::Visual | Self::VisualLine)
+    }
+
     pub fn get_style(&self) -> Style {
         match self {
             Self::Nav => Style::default().fg(Color::Cyan),
             Self::Insert => Style::default().fg(Color::Yellow),
+            Self::Visual | Self::VisualLine => Style::default().fg(Color::Magenta),
             Self::TreeNav => Style::default().fg(Color::Black).bg(Color::Cyan),
+            Self::Finder => Style::default().fg(Color::Black).bg(Color::Yellow),
+            Self::ProjectSearch => Style::default().fg(Color::Black).bg(Color::Green),
+            Self::Outline => Style::default().fg(Color::Black).bg(Color::Magenta),
+            Self::Search => Style::default().fg(Color::Green),
+            Self::Confirm => Style::default().fg(Color::Black).bg(Color::Yellow),
             Self::Command { .. } => Style::default().fg(Color::Red),
         }
     }
@@ -42,7 +60,14 @@ impl Display for EditorMode {
         f.write_str(match self {
             Self::Nav => "NAV",
             Self::Insert => "INSERT",
+            Self::Visual => "VISUAL",
+            Self::VisualLine => "V-LINE",
             Self::TreeNav => "TREE",
+            Self::Finder => "FIND",
+            Self::ProjectSearch => "GREP",
+            Self::Outline => "OUTLINE",
+            Self::Search => "SEARCH",
+            Self::Confirm => "CONFIRM",
             Self::Command { .. } => "COMMAND",
         })
     }
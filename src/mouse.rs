@@ -1,7 +1,10 @@
 use anyhow::Result;
 use crossterm::event::{MouseEvent, MouseEventKind};
 
-use crate::editor::Editor;
+use crate::{
+    context::{self, MAX_STICKY_LINES},
+    editor::Editor,
+};
 
 #[derive(Debug, Clone)]
 pub struct MouseConfig {
@@ -39,11 +42,36 @@ impl MouseHandler {
             MouseEventKind::Down(button) if button.is_left() => {
                 let start_x = editor.editor_start_x as usize;
                 let max_height = editor.editor_max_height;
+                let text_top = 2 + usize::from(editor.show_breadcrumb); // top of window, then the breadcrumb
+                let sticky = editor.sticky_headers;
                 if let Some(buf) = editor.buf_mut() {
-                    if event.row <= max_height {
-                        buf.cursor_y = event.row as usize + buf.scroll_y - 2; // -2 equals top of window
+                    let column = (event.column as usize).saturating_sub(start_x);
+                    if event.row <= max_height
+                        && let Some(screen_line) = (event.row as usize).checked_sub(text_top)
+                    {
+                        let headers = if sticky {
+                            context::sticky(buf, buf.scroll_y, MAX_STICKY_LINES)
+                        } else {
+                            Vec::new()
+                        };
+                        if let Some(header) = headers.get(screen_line) {
+                            buf.cursor_y = header.line;
+                            buf.cursor_x = buf.char_at_visual(
+                                header.line,
+                                0,
+                                buf.visual_columns(header.line, 0).start + column,
+                            );
+                        } else if let Some(&(row, sub)) =
+                            buf.display_lines(screen_line + 1).get(screen_line)
+                        {
+                            buf.cursor_y = row;
+                            buf.cursor_x = buf.char_at_visual(
+                                row,
+                                sub,
+                                buf.visual_columns(row, sub).start + column,
+                            );
+                        }
                     }
-                    buf.cursor_x = (event.column as usize - start_x).min(buf.visible_line_len(buf.cursor_y));
                 }
             }
             // MouseEventKind::Down(MouseButton::Left) => {
@@ -0,0 +1,259 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+};
+use ropey::Rope;
+use std::{cmp::Reverse, ops::Range, path::PathBuf};
+use tree_sitter::{self as ts, StreamingIterator};
+
+use crate::{buffer::Buffer, highlighter::Highlighter, theme::UiTheme};
+
+/// A definition listed in the outline.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// Kind captured by the outline query, e.g. `function` or `impl`.
+    pub kind: String,
+    pub name: String,
+    /// Number of symbols this one is nested in, e.g. 1 for a method.
+    pub depth: usize,
+    /// Line and char column of the name.
+    pub line: usize,
+    pub column: usize,
+    /// Byte range of the whole definition.
+    pub range: Range<usize>,
+}
+
+impl Symbol {
+    /// Short kind shown before the name, as it reads in Rust source.
+    pub fn keyword(&self) -> &str {
+        match self.kind.as_str() {
+            "function" => "fn",
+            "module" => "mod",
+            "heading" => "#",
+            "table" => "[]",
+            "macro" => "macro_rules!",
+            other => other,
+        }
+    }
+}
+
+/// The outline panel: the symbols of the active buffer, filtered by name.
+#[derive(Debug, Default)]
+pub struct Outline {
+    pub symbols: Vec<Symbol>,
+    pub filter: String,
+    /// Position of the selected symbol among the visible ones.
+    pub selected: usize,
+    /// Buffer and revision the symbols were extracted from.
+    computed_for: Option<(Option<PathBuf>, u64)>,
+}
+
+impl Outline {
+    /// Extract the symbols again if the buffer changed since the last call.
+    pub fn refresh(&mut self, buf: &Buffer) {
+        let key = (buf.filepath.clone(), buf.revision);
+        if self.computed_for.as_ref() == Some(&key) {
+            return;
+        }
+        self.computed_for = Some(key);
+        self.symbols = symbols(&buf.highlighter, &buf.text, None);
+        self.selected = self.selected.min(self.visible().len().saturating_sub(1));
+    }
+
+    /// Indices of the symbols whose name contains the filter, along with the
+    /// symbols they are nested in.
+    pub fn visible(&self) -> Vec<usize> {
+        if self.filter.is_empty() {
+            return (0..self.symbols.len()).collect();
+        }
+        let filter = self.filter.to_lowercase();
+        let mut keep = vec![false; self.symbols.len()];
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            if !symbol.name.to_lowercase().contains(&filter) {
+                continue;
+            }
+            keep[i] = true;
+            let mut depth = symbol.depth;
+            for j in (0..i).rev() {
+                if depth == 0 {
+                    break;
+                }
+                if self.symbols[j].depth < depth {
+                    keep[j] = true;
+                    depth = self.symbols[j].depth;
+                }
+            }
+        }
+        (0..self.symbols.len()).filter(|&i| keep[i]).collect()
+    }
+
+    /// Innermost symbol whose definition contains `byte`.
+    pub fn current(&self, byte: usize) -> Option<usize> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.range.contains(&byte))
+            .max_by_key(|(_, s)| s.depth)
+            .map(|(i, _)| i)
+    }
+
+    pub fn selected_symbol(&self) -> Option<&Symbol> {
+        let index = *self.visible().get(self.selected)?;
+        self.symbols.get(index)
+    }
+
+    /// Select the symbol at `byte`, if it is visible.
+    pub fn select_current(&mut self, byte: usize) {
+        if let Some(current) = self.current(byte)
+            && let Some(row) = self.visible().iter().position(|&i| i == current)
+        {
+            self.selected = row;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.visible().len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Draw the visible symbols indented by nesting. The symbol under the
+    /// cursor at `cursor_byte` is highlighted, and the selection while the
+    /// panel has focus.
+    pub fn render(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        focused: bool,
+        cursor_byte: Option<usize>,
+        ui: &UiTheme,
+    ) {
+        let inner_height = area.height.saturating_sub(2) as usize;
+        let visible = self.visible();
+        let current = cursor_byte.and_then(|byte| self.current(byte));
+
+        let mut lines = vec![Line::from(vec![
+            Span::styled("> ", ui.list_prompt),
+            Span::styled(
+                self.filter.clone(),
+                if focused {
+                    ui.list_input
+                } else {
+                    Style::default()
+                },
+            ),
+        ])];
+        if visible.is_empty() {
+            lines.push(Line::from(Span::styled("no symbols", ui.list_prompt)));
+        }
+
+        let list_height = inner_height.saturating_sub(lines.len());
+        let focus_row = if focused {
+            self.selected
+        } else {
+            current
+                .and_then(|c| visible.iter().position(|&i| i == c))
+                .unwrap_or(0)
+        };
+        let scroll = focus_row.saturating_sub(list_height.saturating_sub(1));
+
+        for (row, &i) in visible.iter().enumerate().skip(scroll).take(list_height) {
+            let symbol = &self.symbols[i];
+            let name_style = if focused && row == self.selected {
+                ui.list_selected
+            } else if Some(i) == current {
+                ui.list_current
+            } else {
+                ui.list_item
+            };
+            lines.push(Line::from(vec![
+                Span::raw("  ".repeat(symbol.depth)),
+                Span::styled(format!("{} ", symbol.keyword()), ui.list_kind),
+                Span::styled(symbol.name.clone(), name_style),
+            ]));
+        }
+
+        let block = Block::default()
+            .title(" Outline ")
+            .borders(Borders::ALL)
+            .border_style(if focused { ui.panel_focused } else { ui.border });
+
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+/// Symbols matched by the language's outline query, in document order, or
+/// only those overlapping `within`. A symbol's label runs from its first
+/// `@name` capture to its last, so an impl reads `Display for Buffer`.
+pub fn symbols(
+    highlighter: &Highlighter,
+    text: &Rope,
+    within: Option<Range<usize>>,
+) -> Vec<Symbol> {
+    let (Some(tree), Some(query)) = (
+        highlighter.tree(),
+        highlighter
+            .language()
+            .and_then(|lang| lang.outline.as_ref()),
+    ) else {
+        return Vec::new();
+    };
+    let name_index = query.capture_index_for_name("name");
+
+    let mut symbols = Vec::new();
+    let mut cursor = ts::QueryCursor::new();
+    if let Some(range) = within {
+        cursor.set_byte_range(range);
+    }
+    let mut matches = cursor.matches(query, tree.root_node(), |node: ts::Node| {
+        text.byte_slice(node.byte_range())
+            .chunks()
+            .map(str::as_bytes)
+    });
+    while let Some(m) = matches.next() {
+        let mut names = m
+            .captures
+            .iter()
+            .filter(|c| Some(c.index) == name_index)
+            .map(|c| c.node.byte_range());
+        let Some(item) = m.captures.iter().find(|c| Some(c.index) != name_index) else {
+            continue;
+        };
+        let Some(first) = names.next() else {
+            continue;
+        };
+        let last = names.next_back().unwrap_or(first.clone());
+
+        let name = text
+            .byte_slice(first.start..last.end.max(first.end))
+            .to_string();
+        let name_char = text.byte_to_char(first.start);
+        let line = text.char_to_line(name_char);
+        symbols.push(Symbol {
+            kind: query.capture_names()[item.index as usize].to_string(),
+            name: name.split_whitespace().collect::<Vec<_>>().join(" "),
+            depth: 0,
+            line,
+            column: name_char - text.line_to_char(line),
+            range: item.node.byte_range(),
+        });
+    }
+
+    symbols.sort_by_key(|s| (s.range.start, Reverse(s.range.end)));
+    let mut open: Vec<usize> = Vec::new();
+    for symbol in &mut symbols {
+        while open.last().is_some_and(|&end| end <= symbol.range.start) {
+            open.pop();
+        }
+        symbol.depth = open.len();
+        open.push(symbol.range.end);
+    }
+    symbols
+}
@@ -0,0 +1,456 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+};
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::{substitute::convert_replacement, theme::UiTheme, tree::walk_files};
+
+/// Stop collecting once this many hits were found.
+const MAX_HITS: usize = 2000;
+
+#[derive(Clone)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    pub line: usize,
+    /// Char column of the match start.
+    pub column: usize,
+    /// Byte range of the match within `text`.
+    pub range: (usize, usize),
+    /// Content of the matching line.
+    pub text: String,
+}
+
+impl SearchHit {
+    /// Text replacing this hit's match, with capture groups expanded.
+    /// `None` if the regex no longer matches at the same place.
+    pub fn replacement(&self, regex: &Regex, template: &str) -> Option<String> {
+        let caps = regex.captures_at(&self.text, self.range.0)?;
+        let m = caps.get(0)?;
+        if (m.start(), m.end()) != self.range {
+            return None;
+        }
+        let mut out = String::new();
+        caps.expand(template, &mut out);
+        Some(out)
+    }
+
+    /// The matching line with only this hit replaced.
+    fn replaced_line(&self, regex: &Regex, template: &str) -> Option<String> {
+        let new = self.replacement(regex, template)?;
+        Some(format!(
+            "{}{}{}",
+            &self.text[..self.range.0],
+            new,
+            &self.text[self.range.1..]
+        ))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ProjectSearchState {
+    pub hits: Vec<SearchHit>,
+    pub is_running: bool,
+    pub error: Option<String>,
+    /// Pattern the hits were found with.
+    pub regex: Option<Regex>,
+    /// Incremented for every search so a stale thread can't overwrite the
+    /// results of a newer one.
+    generation: u64,
+}
+
+/// Which part of the panel receives keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchFocus {
+    Query,
+    Hits,
+    Replacement,
+    /// Reviewing the replacements before applying them.
+    Preview,
+}
+
+/// The project search panel: query input, the hits of the last search and
+/// the replace workflow built on them.
+pub struct ProjectSearch {
+    pub root: PathBuf,
+    pub query: String,
+    pub replacement: String,
+    pub focus: SearchFocus,
+    pub selected: usize,
+    /// Per-hit toggle in the replace preview.
+    pub enabled: Vec<bool>,
+    pub state: Arc<Mutex<ProjectSearchState>>,
+}
+
+impl ProjectSearch {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            query: String::new(),
+            replacement: String::new(),
+            focus: SearchFocus::Query,
+            selected: 0,
+            enabled: Vec::new(),
+            state: Arc::new(Mutex::new(ProjectSearchState::default())),
+        }
+    }
+
+    pub fn snapshot(&self) -> ProjectSearchState {
+        self.state.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Start searching the project for the current query in the background.
+    pub fn run(&mut self) {
+        self.selected = 0;
+        self.focus = SearchFocus::Hits;
+        spawn_project_search(&self.state, &self.root, &self.query);
+    }
+
+    pub fn selected_hit(&self) -> Option<SearchHit> {
+        self.state
+            .lock()
+            .ok()
+            .and_then(|s| s.hits.get(self.selected).cloned())
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        let len = self.state.lock().map(|s| s.hits.len()).unwrap_or(0);
+        if self.selected + 1 < len {
+            self.selected += 1;
+        }
+    }
+
+    /// Show the replace preview with every hit enabled. Only possible once
+    /// the search has finished.
+    pub fn start_preview(&mut self) {
+        let state = self.snapshot();
+        if state.is_running || state.hits.is_empty() {
+            return;
+        }
+        self.enabled = vec![true; state.hits.len()];
+        self.focus = SearchFocus::Preview;
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let Some(enabled) = self.enabled.get_mut(self.selected) {
+            *enabled = !*enabled;
+        }
+    }
+
+    /// Enabled replacements grouped by file, as `(hit, new text)` pairs.
+    pub fn planned_replacements(&self) -> BTreeMap<PathBuf, Vec<(SearchHit, String)>> {
+        let state = self.snapshot();
+        let mut plan: BTreeMap<PathBuf, Vec<(SearchHit, String)>> = BTreeMap::new();
+        let Some(regex) = &state.regex else {
+            return plan;
+        };
+        let template = convert_replacement(&self.replacement);
+
+        for (hit, enabled) in state.hits.into_iter().zip(&self.enabled) {
+            if !enabled {
+                continue;
+            }
+            if let Some(new) = hit.replacement(regex, &template) {
+                plan.entry(hit.path.clone()).or_default().push((hit, new));
+            }
+        }
+        plan
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, focused: bool, ui: &UiTheme) {
+        let state = self.snapshot();
+        let inner_height = area.height.saturating_sub(2) as usize;
+        let input_style = |focus: SearchFocus| {
+            if focused && self.focus == focus {
+                ui.list_input
+            } else {
+                Style::default()
+            }
+        };
+
+        let mut lines = vec![Line::from(vec![
+            Span::styled("> ", ui.list_prompt),
+            Span::styled(self.query.clone(), input_style(SearchFocus::Query)),
+        ])];
+
+        let replacing = matches!(self.focus, SearchFocus::Replacement | SearchFocus::Preview);
+        if replacing {
+            lines.push(Line::from(vec![
+                Span::styled("→ ", ui.list_prompt),
+                Span::styled(
+                    self.replacement.clone(),
+                    input_style(SearchFocus::Replacement),
+                ),
+            ]));
+        }
+
+        let status = if state.is_running {
+            Span::styled("⟳ Searching...", ui.list_detail)
+        } else if let Some(err) = &state.error {
+            Span::styled(err.clone(), ui.gutter_error)
+        } else if self.focus == SearchFocus::Preview {
+            let n = self.enabled.iter().filter(|e| **e).count();
+            Span::styled(
+                format!(
+                    "{}/{} selected · space toggle · enter apply",
+                    n,
+                    self.enabled.len()
+                ),
+                ui.list_prompt,
+            )
+        } else {
+            let n = state.hits.len();
+            Span::styled(
+                format!(
+                    "{}{} hit{}",
+                    if n >= MAX_HITS { "≥" } else { "" },
+                    n,
+                    if n == 1 { "" } else { "s" }
+                ),
+                ui.list_prompt,
+            )
+        };
+        lines.push(Line::from(status));
+
+        let list_height = inner_height.saturating_sub(lines.len());
+        let (rows, selected_row) = if self.focus == SearchFocus::Preview {
+            self.preview_rows(&state, ui)
+        } else {
+            self.hit_rows(&state, ui)
+        };
+        let scroll = selected_row.saturating_sub(list_height.saturating_sub(1));
+        lines.extend(rows.into_iter().skip(scroll).take(list_height));
+
+        let block = Block::default()
+            .title(if replacing { " Replace " } else { " Search " })
+            .borders(Borders::ALL)
+            .border_style(if focused { ui.panel_focused } else { ui.border });
+
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    /// One `file:line text` row per hit, with the row of the selected hit.
+    fn hit_rows(&self, state: &ProjectSearchState, ui: &UiTheme) -> (Vec<Line<'static>>, usize) {
+        let rows = state
+            .hits
+            .iter()
+            .enumerate()
+            .map(|(i, hit)| {
+                let rel = hit.path.strip_prefix(&self.root).unwrap_or(&hit.path);
+                let style = if i == self.selected && self.focus == SearchFocus::Hits {
+                    ui.list_selected
+                } else {
+                    ui.list_kind
+                };
+                Line::from(vec![
+                    Span::styled(format!("{}:{} ", rel.display(), hit.line), style),
+                    Span::styled(hit.text.trim().to_string(), ui.list_detail),
+                ])
+            })
+            .collect();
+        (rows, self.selected)
+    }
+
+    /// Per-file diff: a header per file, then the old and new line of each
+    /// hit with its toggle.
+    fn preview_rows(
+        &self,
+        state: &ProjectSearchState,
+        ui: &UiTheme,
+    ) -> (Vec<Line<'static>>, usize) {
+        let mut rows = Vec::new();
+        let mut selected_row = 0;
+        let Some(regex) = &state.regex else {
+            return (rows, selected_row);
+        };
+        let template = convert_replacement(&self.replacement);
+        let mut current_file: Option<&Path> = None;
+
+        for (i, hit) in state.hits.iter().enumerate() {
+            if current_file != Some(hit.path.as_path()) {
+                current_file = Some(&hit.path);
+                let rel = hit.path.strip_prefix(&self.root).unwrap_or(&hit.path);
+                rows.push(Line::from(Span::styled(
+                    rel.display().to_string(),
+                    ui.list_kind.add_modifier(Modifier::BOLD),
+                )));
+            }
+
+            let enabled = self.enabled.get(i).copied().unwrap_or(false);
+            let marker_style = if i == self.selected {
+                ui.list_selected
+            } else {
+                ui.list_prompt
+            };
+            let (old_style, new_style) = if enabled {
+                (ui.list_removed, ui.list_added)
+            } else {
+                let dim = ui.list_prompt;
+                (dim, dim)
+            };
+
+            if i == self.selected {
+                selected_row = rows.len() + 1;
+            }
+            rows.push(Line::from(vec![
+                Span::styled(
+                    format!("{} {:>4} ", if enabled { "[x]" } else { "[ ]" }, hit.line),
+                    marker_style,
+                ),
+                Span::styled(format!("- {}", hit.text.trim()), old_style),
+            ]));
+            let new_line = hit.replaced_line(regex, &template).unwrap_or_default();
+            rows.push(Line::from(vec![
+                Span::raw("         "),
+                Span::styled(format!("+ {}", new_line.trim()), new_style),
+            ]));
+        }
+
+        (rows, selected_row)
+    }
+}
+
+/// Apply replacements to files on disk. Every file is checked and written
+/// to a temporary sibling with the original's permissions first; they are
+/// only moved into place once all of them were written, so a failure to
+/// write leaves every file untouched and no temporary file behind. Hits whose
+/// line changed since the search are skipped, and files left with none are
+/// not rewritten. Returns the number of hits applied and files changed.
+pub fn replace_in_files(
+    plan: &BTreeMap<PathBuf, Vec<(SearchHit, String)>>,
+) -> anyhow::Result<(usize, usize)> {
+    let mut staged = Vec::new();
+    let mut applied = 0;
+
+    let result = (|| -> anyhow::Result<()> {
+        for (path, replacements) in plan {
+            let content = std::fs::read_to_string(path)?;
+            let mut lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+
+            // Back to front so byte ranges on the same line stay valid.
+            let mut file_applied = 0;
+            for (hit, new) in replacements.iter().rev() {
+                let Some(line) = lines.get_mut(hit.line) else {
+                    continue;
+                };
+                let body_len = line.trim_end_matches(['\n', '\r']).len();
+                if line[..body_len] != hit.text {
+                    continue;
+                }
+                line.replace_range(hit.range.0..hit.range.1, new);
+                file_applied += 1;
+            }
+            if file_applied == 0 {
+                continue;
+            }
+            applied += file_applied;
+
+            let tmp = path.with_file_name(format!(
+                ".{}.replace-tmp",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            staged.push((tmp.clone(), path.clone()));
+            std::fs::write(&tmp, lines.concat())?;
+            // Keep the mode of the original, e.g. executable scripts.
+            std::fs::set_permissions(&tmp, std::fs::metadata(path)?.permissions())?;
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        for (tmp, _) in &staged {
+            let _ = std::fs::remove_file(tmp);
+        }
+        return Err(e);
+    }
+
+    for (i, (tmp, path)) in staged.iter().enumerate() {
+        if let Err(e) = std::fs::rename(tmp, path) {
+            for (tmp, _) in &staged[i..] {
+                let _ = std::fs::remove_file(tmp);
+            }
+            return Err(e.into());
+        }
+    }
+    Ok((applied, staged.len()))
+}
+
+fn run_project_search(
+    state: Arc<Mutex<ProjectSearchState>>,
+    root: PathBuf,
+    regex: Regex,
+    generation: u64,
+) {
+    let mut hits = Vec::new();
+
+    'files: for path in walk_files(&root) {
+        // Non UTF-8 files are most likely binary, skip them.
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for (line, text) in content.lines().enumerate() {
+            for m in regex.find_iter(text).filter(|m| !m.is_empty()) {
+                hits.push(SearchHit {
+                    path: path.clone(),
+                    line,
+                    column: text[..m.start()].chars().count(),
+                    range: (m.start(), m.end()),
+                    text: text.to_string(),
+                });
+                if hits.len() >= MAX_HITS {
+                    break 'files;
+                }
+            }
+        }
+    }
+
+    if let Ok(mut s) = state.lock()
+        && s.generation == generation
+    {
+        s.hits = hits;
+        s.regex = Some(regex);
+        s.is_running = false;
+    }
+}
+
+pub fn spawn_project_search(state: &Arc<Mutex<ProjectSearchState>>, root: &Path, pattern: &str) {
+    let regex = match Regex::new(pattern) {
+        Ok(regex) => regex,
+        Err(e) => {
+            if let Ok(mut s) = state.lock() {
+                s.generation += 1;
+                s.hits.clear();
+                s.is_running = false;
+                s.error = Some(format!("invalid pattern: {}", e));
+            }
+            return;
+        }
+    };
+
+    let generation = match state.lock() {
+        Ok(mut s) => {
+            s.generation += 1;
+            s.is_running = true;
+            s.error = None;
+            s.generation
+        }
+        Err(_) => return,
+    };
+
+    let state = Arc::clone(state);
+    let root = root.to_path_buf();
+    thread::spawn(move || run_project_search(state, root, regex, generation));
+}
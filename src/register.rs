@@ -0,0 +1,138 @@
+use std::collections::{HashMap, VecDeque};
+
+/// Number of past yanks and deletes kept, reachable as registers `0`–`9`.
+const HISTORY_LEN: usize = 10;
+
+/// Text captured by a yank or delete. Linewise registers hold whole lines,
+/// always newline-terminated.
+#[derive(Debug, Default, Clone)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+impl Register {
+    pub fn new(text: String, linewise: bool) -> Self {
+        let text = if linewise && !text.ends_with('\n') {
+            text + "\n"
+        } else {
+            text
+        };
+        Self { text, linewise }
+    }
+
+    /// Short description for status messages, e.g. "3 lines".
+    pub fn summary(&self) -> String {
+        if self.linewise {
+            let n = self.text.lines().count();
+            format!("{} line{}", n, if n > 1 { "s" } else { "" })
+        } else {
+            let n = self.text.chars().count();
+            format!("{} char{}", n, if n > 1 { "s" } else { "" })
+        }
+    }
+}
+
+/// Register store: the unnamed register, named `a`–`z` registers and a
+/// history of recent yanks and deletes.
+#[derive(Debug, Default)]
+pub struct Registers {
+    unnamed: Register,
+    named: HashMap<char, Register>,
+    history: VecDeque<Register>,
+    /// Mirror the unnamed register to the system clipboard through OSC 52.
+    pub osc52: bool,
+    /// Text waiting to be sent to the terminal clipboard on the next frame.
+    clipboard: Option<String>,
+}
+
+impl Registers {
+    /// Store a yank or delete in the unnamed register, the history and, if
+    /// given, a named register. An uppercase name appends to the register.
+    pub fn store(&mut self, name: Option<char>, register: Register) {
+        if let Some(name) = name.filter(|c| c.is_ascii_alphabetic()) {
+            let key = name.to_ascii_lowercase();
+            let entry = self.named.entry(key).or_default();
+            if name.is_ascii_uppercase() && !entry.text.is_empty() {
+                entry.text.push_str(&register.text);
+                entry.linewise |= register.linewise;
+            } else {
+                *entry = register.clone();
+            }
+        }
+
+        self.history.push_front(register.clone());
+        self.history.truncate(HISTORY_LEN);
+
+        if self.osc52 {
+            self.clipboard = Some(register.text.clone());
+        }
+        self.unnamed = register;
+    }
+
+    /// Look up a register: unnamed when `name` is `None`, `a`–`z` for named
+    /// registers and `0`–`9` for the history, most recent first.
+    pub fn get(&self, name: Option<char>) -> Option<&Register> {
+        match name {
+            None | Some('"') => Some(&self.unnamed).filter(|r| !r.text.is_empty()),
+            Some(c) if c.is_ascii_digit() => self.history.get(c as usize - '0' as usize),
+            Some(c) => self.named.get(&c.to_ascii_lowercase()),
+        }
+    }
+
+    pub fn take_clipboard(&mut self) -> Option<String> {
+        self.clipboard.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Register {
+        Register::new(text.to_string(), false)
+    }
+
+    #[test]
+    fn linewise_text_ends_with_a_newline() {
+        assert_eq!(Register::new("a".into(), true).text, "a\n");
+        assert_eq!(Register::new("a\n".into(), true).text, "a\n");
+        assert_eq!(Register::new("a\nb".into(), true).summary(), "2 lines");
+        assert_eq!(chars("abc").summary(), "3 chars");
+    }
+
+    #[test]
+    fn named_registers_and_append() {
+        let mut registers = Registers::default();
+        registers.store(Some('a'), chars("one"));
+        registers.store(Some('A'), chars(" two"));
+        registers.store(None, chars("other"));
+        assert_eq!(registers.get(Some('a')).unwrap().text, "one two");
+        assert_eq!(registers.get(Some('A')).unwrap().text, "one two");
+        assert_eq!(registers.get(None).unwrap().text, "other");
+        assert_eq!(registers.get(Some('"')).unwrap().text, "other");
+        assert!(registers.get(Some('b')).is_none());
+    }
+
+    #[test]
+    fn history_keeps_the_latest() {
+        let mut registers = Registers::default();
+        assert!(registers.get(None).is_none());
+        for i in 0..12 {
+            registers.store(None, chars(&i.to_string()));
+        }
+        assert_eq!(registers.get(Some('0')).unwrap().text, "11");
+        assert_eq!(registers.get(Some('9')).unwrap().text, "2");
+    }
+
+    #[test]
+    fn clipboard_only_when_enabled() {
+        let mut registers = Registers::default();
+        registers.store(None, chars("x"));
+        assert_eq!(registers.take_clipboard(), None);
+        registers.osc52 = true;
+        registers.store(None, chars("y"));
+        assert_eq!(registers.take_clipboard().as_deref(), Some("y"));
+        assert_eq!(registers.take_clipboard(), None);
+    }
+}
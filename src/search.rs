@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::buffer::Buffer;
+
+/// In-buffer search state: the pattern, its direction and the matches found
+/// in the active buffer.
+#[derive(Debug, Default)]
+pub struct Search {
+    pub query: String,
+    pub forward: bool,
+    /// Cursor `(x, y)` when the search was started, restored on cancel.
+    pub origin: (usize, usize),
+    /// Char ranges of every match, in order.
+    pub matches: Vec<(usize, usize)>,
+    /// Buffer, revision and query the matches were computed for.
+    computed_for: Option<(Option<PathBuf>, u64, String)>,
+}
+
+impl Search {
+    pub fn start(&mut self, buf: &Buffer, forward: bool) {
+        self.query.clear();
+        self.forward = forward;
+        self.origin = (buf.cursor_x, buf.cursor_y);
+        self.matches.clear();
+        self.computed_for = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.matches.clear();
+        self.computed_for = None;
+    }
+
+    /// Recompute the matches if the buffer or the query changed since the
+    /// last call.
+    pub fn refresh(&mut self, buf: &Buffer) {
+        let key = (buf.filepath.clone(), buf.revision, self.query.clone());
+        if self.computed_for.as_ref() == Some(&key) {
+            return;
+        }
+        self.computed_for = Some(key);
+        self.matches.clear();
+
+        if self.query.is_empty() {
+            return;
+        }
+
+        let regex = search_regex(&self.query);
+        let source = buf.text.to_string();
+        self.matches = regex
+            .find_iter(&source)
+            .filter(|m| !m.is_empty())
+            .map(|m| {
+                (
+                    buf.text.byte_to_char(m.start()),
+                    buf.text.byte_to_char(m.end()),
+                )
+            })
+            .collect();
+    }
+
+    /// Index of the first match after `pos` (before it when going backward),
+    /// wrapping around the end of the buffer. The flag is set on wrap.
+    pub fn find_from(&self, pos: usize, forward: bool) -> Option<(usize, bool)> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let after = self.matches.partition_point(|(start, _)| *start <= pos);
+        if forward {
+            if after < self.matches.len() {
+                Some((after, false))
+            } else {
+                Some((0, true))
+            }
+        } else {
+            let before = self.matches.partition_point(|(start, _)| *start < pos);
+            if before > 0 {
+                Some((before - 1, false))
+            } else {
+                Some((self.matches.len() - 1, true))
+            }
+        }
+    }
+
+    /// 1-based index of the match under or last before `pos`, or of the
+    /// first match, which `n` goes to next, when `pos` is before all of them.
+    pub fn index_at(&self, pos: usize) -> usize {
+        self.matches
+            .partition_point(|(start, _)| *start <= pos)
+            .max(1)
+    }
+
+    /// Matches overlapping the chars `start..end`.
+    pub fn matches_in(&self, start: usize, end: usize) -> &[(usize, usize)] {
+        let from = self.matches.partition_point(|(_, e)| *e <= start);
+        let to = self.matches.partition_point(|(s, _)| *s < end);
+        &self.matches[from..to.max(from)]
+    }
+}
+
+/// Compile a search pattern with `^`/`$` matching at line boundaries. While
+/// a pattern is still being typed it may not be valid yet, in which case it
+/// is searched for literally.
+pub fn search_regex(pattern: &str) -> Regex {
+    RegexBuilder::new(pattern)
+        .multi_line(true)
+        .build()
+        .or_else(|_| {
+            RegexBuilder::new(&regex::escape(pattern))
+                .multi_line(true)
+                .build()
+        })
+        .expect("escaped pattern is a valid regex")
+}
@@ -0,0 +1,318 @@
+use anyhow::{Result, anyhow, bail};
+use regex::{Regex, RegexBuilder};
+
+use crate::buffer::Buffer;
+
+/// A parsed `:[range]s/pattern/replacement/[flags]` command.
+pub struct Substitute {
+    regex: Regex,
+    replacement: String,
+    /// First and last line (inclusive) the command applies to.
+    lines: (usize, usize),
+    global: bool,
+    pub confirm: bool,
+}
+
+/// A single replacement: the char range to replace and its new text.
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Interactive state of a `:s///c` command stepping through its matches.
+#[derive(Debug, Default)]
+pub struct ConfirmState {
+    pub candidates: Vec<Replacement>,
+    pub index: usize,
+    pub accepted: Vec<Replacement>,
+}
+
+impl ConfirmState {
+    pub fn current(&self) -> Option<&Replacement> {
+        self.candidates.get(self.index)
+    }
+
+    /// Accept or skip the current candidate and move to the next one.
+    /// Returns false once every candidate has been seen.
+    pub fn answer(&mut self, accept: bool) -> bool {
+        if accept && let Some(r) = self.current() {
+            self.accepted.push(r.clone());
+        }
+        self.index += 1;
+        self.index < self.candidates.len()
+    }
+
+    pub fn accept_rest(&mut self) {
+        self.accepted
+            .extend_from_slice(&self.candidates[self.index..]);
+        self.index = self.candidates.len();
+    }
+}
+
+impl Substitute {
+    /// Parse a substitute command. Returns `None` if `cmd` isn't one, so the
+    /// caller can try other commands. Line numbers are the ones shown in the
+    /// gutter; without a range only the cursor line is affected.
+    /// `last_pattern` is used when the pattern is left empty.
+    pub fn parse(
+        cmd: &str,
+        cursor_line: usize,
+        last_line: usize,
+        last_pattern: &str,
+    ) -> Option<Result<Self>> {
+        let (lines, rest) = parse_range(cmd, cursor_line, last_line)?;
+        let rest = rest.strip_prefix('s')?;
+        let delim = rest.chars().next()?;
+        if delim.is_alphanumeric() || delim.is_whitespace() || delim == '\\' {
+            return None;
+        }
+        Some(Self::parse_body(
+            &rest[delim.len_utf8()..],
+            delim,
+            lines,
+            last_pattern,
+        ))
+    }
+
+    fn parse_body(
+        body: &str,
+        delim: char,
+        lines: (usize, usize),
+        last_pattern: &str,
+    ) -> Result<Self> {
+        let mut parts = split_unescaped(body, delim).into_iter();
+        let pattern = parts.next().unwrap_or_default();
+        let replacement = parts.next().unwrap_or_default();
+        let flags = parts.next().unwrap_or_default();
+        if parts.next().is_some() {
+            bail!("trailing characters");
+        }
+
+        let pattern = if pattern.is_empty() {
+            last_pattern.to_string()
+        } else {
+            pattern
+        };
+        if pattern.is_empty() {
+            bail!("no previous pattern");
+        }
+
+        let mut builder = RegexBuilder::new(&pattern);
+        builder.multi_line(true);
+        let mut global = false;
+        let mut confirm = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'c' => confirm = true,
+                'i' => {
+                    builder.case_insensitive(true);
+                }
+                'I' => {
+                    builder.case_insensitive(false);
+                }
+                other => bail!("unknown flag: {}", other),
+            }
+        }
+
+        let regex = builder
+            .build()
+            .map_err(|e| anyhow!("invalid pattern: {}", e))?;
+
+        Ok(Self {
+            regex,
+            replacement: convert_replacement(&replacement),
+            lines,
+            global,
+            confirm,
+        })
+    }
+
+    /// Every replacement this command makes in `buf`, in text order.
+    pub fn replacements(&self, buf: &Buffer) -> Vec<Replacement> {
+        let text = &buf.text;
+        let (first, last) = self.lines;
+        if first >= text.len_lines() {
+            return Vec::new();
+        }
+        let last = last.min(text.len_lines() - 1);
+        let start_byte = text.line_to_byte(first);
+        let end_byte = text.line_to_byte(last) + text.line(last).len_bytes();
+
+        let source = text.to_string();
+        let mut result = Vec::new();
+        let mut last_line_hit = None;
+
+        for caps in self.regex.captures_iter(&source) {
+            let m = caps.get(0).expect("group 0 always matches");
+            if m.start() < start_byte {
+                continue;
+            }
+            if m.start() >= end_byte || m.end() > end_byte {
+                break;
+            }
+            let line = text.byte_to_line(m.start());
+            if !self.global && last_line_hit == Some(line) {
+                continue;
+            }
+            last_line_hit = Some(line);
+
+            let mut replaced = String::new();
+            caps.expand(&self.replacement, &mut replaced);
+            result.push(Replacement {
+                start: text.byte_to_char(m.start()),
+                end: text.byte_to_char(m.end()),
+                text: replaced,
+            });
+        }
+
+        result
+    }
+}
+
+/// Parse a leading line range: `%`, `N`, `N,M`, where either side may be
+/// `.` (cursor line) or `$` (last line).
+pub fn parse_range(
+    cmd: &str,
+    cursor_line: usize,
+    last_line: usize,
+) -> Option<((usize, usize), &str)> {
+    if let Some(rest) = cmd.strip_prefix('%') {
+        return Some(((0, last_line), rest));
+    }
+
+    let address = |s: &str| -> Option<(usize, usize)> {
+        // Returns the line and the number of bytes consumed.
+        match s.chars().next()? {
+            '.' => Some((cursor_line, 1)),
+            '$' => Some((last_line, 1)),
+            c if c.is_ascii_digit() => {
+                let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+                Some((s[..len].parse().ok()?, len))
+            }
+            _ => None,
+        }
+    };
+
+    let Some((first, len)) = address(cmd) else {
+        return Some(((cursor_line, cursor_line), cmd));
+    };
+    let rest = &cmd[len..];
+    match rest.strip_prefix(',') {
+        Some(after) => {
+            let (second, len) = address(after)?;
+            let (a, b) = (first.min(second), first.max(second));
+            Some(((a, b), &after[len..]))
+        }
+        None => Some(((first, first), rest)),
+    }
+}
+
+/// Split on `delim`, treating `\<delim>` as a literal delimiter. Other
+/// escapes are kept for the regex and replacement parsers.
+fn split_unescaped(s: &str, delim: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().expect("parts is never empty");
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delim => part.push(next),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delim => parts.push(String::new()),
+            c => part.push(c),
+        }
+    }
+    parts
+}
+
+/// Turn a Vim-style replacement (`\1`, `&`, `\n`) into the syntax expected
+/// by `Captures::expand`. `$1` and `${name}` keep working as they are.
+pub fn convert_replacement(repl: &str) -> String {
+    let mut out = String::new();
+    let mut chars = repl.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => out.push_str("${0}"),
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => {
+                    out.push_str(&format!("${{{}}}", d));
+                }
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('&') => out.push('&'),
+                Some('$') => out.push_str("$$"),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_addresses() {
+        assert_eq!(parse_range("%s/a/b/", 4, 9), Some(((0, 9), "s/a/b/")));
+        assert_eq!(parse_range("s/a/b/", 4, 9), Some(((4, 4), "s/a/b/")));
+        assert_eq!(parse_range("3s/a/b/", 4, 9), Some(((3, 3), "s/a/b/")));
+        assert_eq!(parse_range(".,$s/a/b/", 4, 9), Some(((4, 9), "s/a/b/")));
+        assert_eq!(
+            parse_range("12,5reindent", 4, 20),
+            Some(((5, 12), "reindent"))
+        );
+        assert_eq!(parse_range("3,s/a/b/", 4, 9), None);
+    }
+
+    #[test]
+    fn replacement_syntax() {
+        assert_eq!(convert_replacement(r"\2-\1"), "${2}-${1}");
+        assert_eq!(convert_replacement("<&>"), "<${0}>");
+        assert_eq!(convert_replacement(r"a\nb\tc"), "a\nb\tc");
+        assert_eq!(convert_replacement(r"\&\$"), "&$$");
+        assert_eq!(convert_replacement("$1"), "$1");
+
+        let regex = Regex::new(r"(\w+) (\w+)").unwrap();
+        let template = convert_replacement(r"\2 \1 [&]");
+        assert_eq!(
+            regex.replace("hello world", template.as_str()),
+            "world hello [hello world]"
+        );
+    }
+
+    #[test]
+    fn escaped_delimiters() {
+        assert_eq!(
+            split_unescaped(r"a\/b/c\d/g", '/'),
+            vec!["a/b", r"c\d", "g"]
+        );
+        assert_eq!(split_unescaped(r"x#y\#z", '#'), vec!["x", "y#z"]);
+        assert_eq!(split_unescaped(r"end\", '/'), vec![r"end\"]);
+    }
+
+    #[test]
+    fn parse_commands() {
+        let sub = Substitute::parse(r"%s#a\#b#c#gc", 0, 5, "")
+            .unwrap()
+            .unwrap();
+        assert_eq!(sub.regex.as_str(), "a#b");
+        assert_eq!(sub.lines, (0, 5));
+        assert!(sub.global && sub.confirm);
+
+        let sub = Substitute::parse("s//x/", 2, 5, "last").unwrap().unwrap();
+        assert_eq!(sub.regex.as_str(), "last");
+        assert!(Substitute::parse("s/a/b/x", 0, 5, "").unwrap().is_err());
+        assert!(Substitute::parse("set wrap", 0, 5, "").is_none());
+    }
+}
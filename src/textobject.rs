@@ -0,0 +1,161 @@
+use ropey::Rope;
+use std::ops::Range;
+use tree_sitter::{self as ts, StreamingIterator};
+
+use crate::highlighter::Highlighter;
+
+/// Text object for the key typed after `i` or `a`, named like the captures of
+/// the `textobjects` query.
+pub fn object_for_key(key: char) -> Option<&'static str> {
+    Some(match key {
+        'f' => "function",
+        'a' => "parameter",
+        's' => "string",
+        'c' => "comment",
+        _ => return None,
+    })
+}
+
+/// Motion target for the key typed after `]` or `[`.
+pub fn motion_for_key(key: char) -> Option<&'static str> {
+    Some(match key {
+        'f' => "function",
+        'i' => "impl",
+        's' => "struct",
+        't' => "test",
+        _ => return None,
+    })
+}
+
+/// Start byte of the nearest `object` after `byte`, or before it when
+/// `forward` is false.
+pub fn next_start(
+    highlighter: &Highlighter,
+    text: &Rope,
+    object: &str,
+    byte: usize,
+    forward: bool,
+) -> Option<usize> {
+    let starts = objects(highlighter, text, object)
+        .into_iter()
+        .map(|(around, _)| around.start_byte());
+    if forward {
+        starts.filter(|&start| start > byte).min()
+    } else {
+        starts.filter(|&start| start < byte).max()
+    }
+}
+
+/// Byte range of the innermost `object` containing `byte`: all of it, or its
+/// contents with `inside`. Objects without separate contents are selected
+/// whole either way.
+pub fn select(
+    highlighter: &Highlighter,
+    text: &Rope,
+    object: &str,
+    byte: usize,
+    inside: bool,
+) -> Option<Range<usize>> {
+    let (around, contents) = objects(highlighter, text, object)
+        .into_iter()
+        .filter(|(around, _)| around.start_byte() <= byte && byte < around.end_byte())
+        .min_by_key(|(around, _)| around.end_byte() - around.start_byte())?;
+
+    let range = match (inside, contents) {
+        (true, Some(contents)) => delimited_contents(contents, text),
+        (false, _) if object == "parameter" => with_separator(around),
+        _ => around.byte_range(),
+    };
+    (!range.is_empty()).then_some(range)
+}
+
+/// `(around, inside)` nodes of every match of `object` in the tree.
+fn objects<'t>(
+    highlighter: &'t Highlighter,
+    text: &Rope,
+    object: &str,
+) -> Vec<(ts::Node<'t>, Option<ts::Node<'t>>)> {
+    let (Some(tree), Some(query)) = (
+        highlighter.tree(),
+        highlighter
+            .language()
+            .and_then(|lang| lang.textobjects.as_ref()),
+    ) else {
+        return Vec::new();
+    };
+    let Some(around) = query.capture_index_for_name(&format!("{}.around", object)) else {
+        return Vec::new();
+    };
+    let inside = query.capture_index_for_name(&format!("{}.inside", object));
+
+    let mut found = Vec::new();
+    let mut cursor = ts::QueryCursor::new();
+    let mut matches = cursor.matches(query, tree.root_node(), |node: ts::Node| {
+        text.byte_slice(node.byte_range())
+            .chunks()
+            .map(str::as_bytes)
+    });
+    while let Some(m) = matches.next() {
+        let node = |index: u32| m.captures.iter().find(|c| c.index == index).map(|c| c.node);
+        if let Some(node_around) = node(around) {
+            found.push((node_around, inside.and_then(node)));
+        }
+    }
+    found
+}
+
+/// `node` without the unnamed delimiters at its ends, such as braces or
+/// quotes. Inside brackets, the line breaks right after the opening one and
+/// before the closing one are left out too, so deleting the contents of a
+/// block keeps its braces on their own lines.
+fn delimited_contents(node: ts::Node, text: &Rope) -> Range<usize> {
+    let mut range = node.byte_range();
+    let mut cursor = node.walk();
+    let children: Vec<ts::Node> = node.children(&mut cursor).collect();
+    let [first, .., last] = children[..] else {
+        return range;
+    };
+    if !first.is_named() {
+        range.start = first.end_byte();
+    }
+    if !last.is_named() {
+        range.end = last.start_byte();
+    }
+    if !matches!(first.kind(), "{" | "(" | "[") {
+        return range;
+    }
+
+    let contents = text.byte_slice(range.clone()).to_string();
+    let start = match contents.find('\n') {
+        Some(nl) if contents[..nl].trim().is_empty() => nl + 1,
+        _ => 0,
+    };
+    let end = match contents.rfind('\n') {
+        Some(nl) if nl + 1 >= start && contents[nl + 1..].trim().is_empty() => nl + 1,
+        _ => contents.len(),
+    };
+    range.start + start..range.start + end.max(start)
+}
+
+/// `node` with the comma after it, up to the next item, or the comma before
+/// it when it is the last item.
+fn with_separator(node: ts::Node) -> Range<usize> {
+    if let Some(next) = node.next_sibling()
+        && next.kind() == ","
+    {
+        let end = match next.next_sibling() {
+            Some(item) if item.is_named() => item.start_byte(),
+            _ => next.end_byte(),
+        };
+        return node.start_byte()..end;
+    }
+    if let Some(prev) = node.prev_sibling()
+        && prev.kind() == ","
+    {
+        let start = prev
+            .prev_sibling()
+            .map_or(prev.start_byte(), |item| item.end_byte());
+        return start..node.end_byte();
+    }
+    node.byte_range()
+}
@@ -0,0 +1,543 @@
+use anyhow::{Context, bail};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf, str::FromStr};
+
+use crate::config;
+
+/// Themes compiled into the editor, usable without any config files.
+pub const BUILTIN_THEMES: &[&str] = &["dark-modern", "light-modern"];
+
+/// How many `inherits` links are followed before giving up on a cycle.
+const MAX_INHERITS: usize = 8;
+
+/// Colours of the editor chrome around the text.
+#[derive(Clone)]
+pub struct UiTheme {
+    /// Base style of the whole screen; themes for light terminals set a bg.
+    pub background: Style,
+    pub gutter: Style,
+    pub gutter_error: Style,
+    pub gutter_warning: Style,
+    /// Summary shown after the first line of a closed fold.
+    pub fold: Style,
+    /// Enclosing items of the cursor, above the text.
+    pub breadcrumb: Style,
+    /// Headers of enclosing items pinned at the top of the text.
+    pub sticky: Style,
+    pub selection: Style,
+    pub search_match: Style,
+    pub search_current: Style,
+    pub border: Style,
+    pub tab_active: Style,
+    pub tab_inactive: Style,
+    pub status: Style,
+    pub status_file: Style,
+    /// Secondary status bar text: language, indent settings and messages.
+    pub status_info: Style,
+    /// `cargo check` still running, in the status bar and diagnostics panel.
+    pub status_checking: Style,
+    pub status_error: Style,
+    pub status_warning: Style,
+    pub status_ok: Style,
+    /// `L12:5` location of an entry in the diagnostics panel.
+    pub diag_location: Style,
+    pub tree_dir: Style,
+    pub tree_file: Style,
+    pub tree_selected_dir: Style,
+    pub tree_selected_file: Style,
+    pub tree_icon: Style,
+    /// Border of the side panel or popup that has focus.
+    pub panel_focused: Style,
+    /// `> ` before a list's filter, and hints such as hit counts.
+    pub list_prompt: Style,
+    /// Filter text while its list has focus.
+    pub list_input: Style,
+    pub list_item: Style,
+    pub list_selected: Style,
+    /// Outline symbol under the cursor.
+    pub list_current: Style,
+    /// Chars matched by the finder's fuzzy query.
+    pub list_match: Style,
+    /// Symbol keywords and file names in front of list items.
+    pub list_kind: Style,
+    /// Secondary text: file previews and the text of search hits.
+    pub list_detail: Style,
+    pub list_removed: Style,
+    pub list_added: Style,
+}
+
+impl UiTheme {
+    fn slot(&mut self, key: &str) -> Option<&mut Style> {
+        Some(match key {
+            "background" => &mut self.background,
+            "gutter" => &mut self.gutter,
+            "gutter_error" => &mut self.gutter_error,
+            "gutter_warning" => &mut self.gutter_warning,
+            "fold" => &mut self.fold,
+            "breadcrumb" => &mut self.breadcrumb,
+            "sticky" => &mut self.sticky,
+            "selection" => &mut self.selection,
+            "search_match" => &mut self.search_match,
+            "search_current" => &mut self.search_current,
+            "border" => &mut self.border,
+            "tab_active" => &mut self.tab_active,
+            "tab_inactive" => &mut self.tab_inactive,
+            "status" => &mut self.status,
+            "status_file" => &mut self.status_file,
+            "status_info" => &mut self.status_info,
+            "status_checking" => &mut self.status_checking,
+            "status_error" => &mut self.status_error,
+            "status_warning" => &mut self.status_warning,
+            "status_ok" => &mut self.status_ok,
+            "diag_location" => &mut self.diag_location,
+            "tree_dir" => &mut self.tree_dir,
+            "tree_file" => &mut self.tree_file,
+            "tree_selected_dir" => &mut self.tree_selected_dir,
+            "tree_selected_file" => &mut self.tree_selected_file,
+            "tree_icon" => &mut self.tree_icon,
+            "panel_focused" => &mut self.panel_focused,
+            "list_prompt" => &mut self.list_prompt,
+            "list_input" => &mut self.list_input,
+            "list_item" => &mut self.list_item,
+            "list_selected" => &mut self.list_selected,
+            "list_current" => &mut self.list_current,
+            "list_match" => &mut self.list_match,
+            "list_kind" => &mut self.list_kind,
+            "list_detail" => &mut self.list_detail,
+            "list_removed" => &mut self.list_removed,
+            "list_added" => &mut self.list_added,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    /// Styles keyed by highlight capture name, e.g. `function.method`.
+    styles: HashMap<String, Style>,
+    /// Plain text, and any capture the theme has no style for.
+    pub default: Style,
+    pub ui: UiTheme,
+}
+
+impl Theme {
+    pub fn vscode_dark_modern() -> Self {
+        let mut styles = HashMap::new();
+
+        // ── VS Code Dark Modern — faithful palette ──────────────────────
+        let keyword = Color::Rgb(86, 156, 214); // #569CD6  — keywords
+        let control = Color::Rgb(197, 134, 192); // #C586C0  — control flow
+        let function = Color::Rgb(220, 220, 170); // #DCDCAA  — functions / macros
+        let type_c = Color::Rgb(78, 201, 176); // #4EC9B0  — types / traits
+        let string = Color::Rgb(206, 145, 120); // #CE9178  — strings
+        let escape = Color::Rgb(215, 186, 125); // #D7BA7D  — escape sequences
+        let number = Color::Rgb(181, 206, 168); // #B5CEA8  — numeric literals
+        let comment = Color::Rgb(106, 153, 85); // #6A9955  — comments (italic)
+        let doc_com = Color::Rgb(127, 178, 103); // #7FB267  — doc comments (brighter green)
+        let variable = Color::Rgb(156, 220, 254); // #9CDCFE  — variables / params
+        let constant = Color::Rgb(79, 193, 255); // #4FC1FF  — constants / enum variants
+        let attribute = Color::Rgb(156, 220, 254); // #9CDCFE  — attributes
+        let lifetime = Color::Rgb(86, 156, 214); // #569CD6  — lifetimes
+        let punct = Color::Rgb(212, 212, 212); // #D4D4D4  — punctuation / operators
+        let default = Color::Rgb(212, 212, 212); // #D4D4D4  — plain text
+        let namespace = Color::Rgb(78, 201, 176); // #4EC9B0  — modules / crates
+        let list = Color::Rgb(103, 150, 230); // #6796E6  — markdown list markers
+
+        // Helper — VS Code Dark Modern uses NO bold for most tokens
+        let s = |fg: Color| Style::default().fg(fg);
+        let si = |fg: Color| Style::default().fg(fg).add_modifier(Modifier::ITALIC);
+        let mut set = |name: &str, style: Style| styles.insert(name.to_string(), style);
+
+        set("keyword", s(keyword));
+        set("keyword.control", s(control));
+
+        set("function", s(function));
+        set("function.macro", s(function));
+
+        set("type", s(type_c));
+        set("constructor", s(constant));
+        set("constant", s(constant));
+        set("module", s(namespace));
+
+        set("string", s(string));
+        set("string.escape", s(escape));
+        set("number", s(number));
+        // VS Code treats true/false as keyword-blue
+        set("boolean", s(keyword));
+
+        set("comment", si(comment));
+        set("comment.documentation", si(doc_com));
+
+        set("variable", s(variable));
+        set("variable.builtin", s(keyword));
+        set("property", s(variable));
+        set("attribute", si(attribute));
+        set("label", s(lifetime));
+
+        set("operator", s(punct));
+        set("punctuation", s(punct));
+
+        // Markdown
+        set("markup.heading", s(keyword).add_modifier(Modifier::BOLD));
+        set("markup.raw", s(string));
+        set(
+            "markup.link.url",
+            s(string).add_modifier(Modifier::UNDERLINED),
+        );
+        set("markup.link.label", s(variable));
+        set("markup.list", s(list));
+        set("markup.quote", si(comment));
+        set("markup.bold", Style::default().add_modifier(Modifier::BOLD));
+        set(
+            "markup.italic",
+            Style::default().add_modifier(Modifier::ITALIC),
+        );
+        set(
+            "markup.strikethrough",
+            Style::default().add_modifier(Modifier::CROSSED_OUT),
+        );
+
+        // ── Chrome — terminal colours, on the terminal's own background ─
+        let ui = UiTheme {
+            background: Style::default(),
+            gutter: s(Color::DarkGray),
+            gutter_error: s(Color::Red),
+            gutter_warning: s(Color::Yellow),
+            fold: s(Color::DarkGray).bg(Color::Rgb(45, 45, 45)),
+            breadcrumb: s(Color::Gray),
+            sticky: Style::default().bg(Color::Rgb(37, 37, 38)),
+            selection: Style::default().bg(Color::Rgb(38, 79, 120)),
+            search_match: Style::default().bg(Color::Rgb(98, 51, 21)),
+            search_current: Style::default().bg(Color::Rgb(81, 92, 106)),
+            border: s(Color::DarkGray),
+            tab_active: Style::default()
+                .fg(Color::Black)
+                .bg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            tab_inactive: s(Color::DarkGray),
+            status: Style::default(),
+            status_file: Style::default().fg(Color::Black).bg(Color::White),
+            status_info: s(Color::Gray),
+            status_checking: s(Color::Gray),
+            status_error: s(Color::Red),
+            status_warning: s(Color::Yellow),
+            status_ok: s(Color::Green),
+            diag_location: s(Color::DarkGray),
+            tree_dir: s(Color::Cyan).add_modifier(Modifier::BOLD),
+            tree_file: s(Color::White),
+            tree_selected_dir: Style::default()
+                .fg(Color::Black)
+                .bg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+            tree_selected_file: Style::default().fg(Color::Black).bg(Color::White),
+            tree_icon: s(Color::DarkGray),
+            panel_focused: s(Color::Cyan).add_modifier(Modifier::BOLD),
+            list_prompt: s(Color::DarkGray),
+            list_input: s(Color::Yellow),
+            list_item: s(Color::White),
+            list_selected: Style::default().fg(Color::Black).bg(Color::White),
+            list_current: s(Color::Yellow).add_modifier(Modifier::BOLD),
+            list_match: s(Color::Yellow).add_modifier(Modifier::BOLD),
+            list_kind: s(Color::Cyan),
+            list_detail: s(Color::Gray),
+            list_removed: s(Color::Red),
+            list_added: s(Color::Green),
+        };
+
+        Self {
+            name: "dark-modern".to_string(),
+            styles,
+            default: Style::default().fg(default),
+            ui,
+        }
+    }
+
+    pub fn vscode_light_modern() -> Self {
+        let mut styles = HashMap::new();
+
+        // ── VS Code Light Modern ────────────────────────────────────────
+        let keyword = Color::Rgb(0, 0, 255); // #0000FF  — keywords
+        let control = Color::Rgb(175, 0, 219); // #AF00DB  — control flow
+        let function = Color::Rgb(121, 94, 38); // #795E26  — functions / macros
+        let type_c = Color::Rgb(38, 127, 153); // #267F99  — types / traits
+        let string = Color::Rgb(163, 21, 21); // #A31515  — strings
+        let escape = Color::Rgb(238, 0, 0); // #EE0000  — escape sequences
+        let number = Color::Rgb(9, 134, 88); // #098658  — numeric literals
+        let comment = Color::Rgb(0, 128, 0); // #008000  — comments (italic)
+        let variable = Color::Rgb(0, 16, 128); // #001080  — variables / params
+        let constant = Color::Rgb(0, 112, 193); // #0070C1  — constants / enum variants
+        let text = Color::Rgb(59, 59, 59); // #3B3B3B  — plain text / punctuation
+        let list = Color::Rgb(4, 81, 165); // #0451A5  — markdown list markers
+        let accent = Color::Rgb(0, 95, 184); // #005FB8  — active tab / status
+        let dim = Color::Rgb(110, 118, 129); // #6E7681  — line numbers, inactive tabs
+
+        let s = |fg: Color| Style::default().fg(fg);
+        let si = |fg: Color| Style::default().fg(fg).add_modifier(Modifier::ITALIC);
+        let mut set = |name: &str, style: Style| styles.insert(name.to_string(), style);
+
+        set("keyword", s(keyword));
+        set("keyword.control", s(control));
+        set("function", s(function));
+        set("function.macro", s(function));
+        set("type", s(type_c));
+        set("constructor", s(constant));
+        set("constant", s(constant));
+        set("module", s(type_c));
+        set("string", s(string));
+        set("string.escape", s(escape));
+        set("number", s(number));
+        set("boolean", s(keyword));
+        set("comment", si(comment));
+        set("comment.documentation", si(comment));
+        set("variable", s(variable));
+        set("variable.builtin", s(keyword));
+        set("property", s(variable));
+        set("attribute", si(variable));
+        set("label", s(keyword));
+        set("operator", s(text));
+        set("punctuation", s(text));
+        set("markup.heading", s(accent).add_modifier(Modifier::BOLD));
+        set("markup.raw", s(string));
+        set(
+            "markup.link.url",
+            s(string).add_modifier(Modifier::UNDERLINED),
+        );
+        set("markup.link.label", s(variable));
+        set("markup.list", s(list));
+        set("markup.quote", si(comment));
+        set("markup.bold", Style::default().add_modifier(Modifier::BOLD));
+        set(
+            "markup.italic",
+            Style::default().add_modifier(Modifier::ITALIC),
+        );
+        set(
+            "markup.strikethrough",
+            Style::default().add_modifier(Modifier::CROSSED_OUT),
+        );
+
+        let white = Color::Rgb(255, 255, 255);
+        let ui = UiTheme {
+            background: Style::default().fg(text).bg(white),
+            gutter: s(dim),
+            gutter_error: s(Color::Rgb(229, 20, 0)),
+            gutter_warning: s(Color::Rgb(191, 136, 3)),
+            fold: s(dim).bg(Color::Rgb(234, 234, 234)),
+            breadcrumb: s(dim),
+            sticky: Style::default().bg(Color::Rgb(243, 243, 243)),
+            selection: Style::default().bg(Color::Rgb(173, 214, 255)),
+            search_match: Style::default().bg(Color::Rgb(246, 205, 179)),
+            search_current: Style::default().bg(Color::Rgb(168, 172, 148)),
+            border: s(Color::Rgb(200, 200, 200)),
+            tab_active: Style::default()
+                .fg(white)
+                .bg(accent)
+                .add_modifier(Modifier::BOLD),
+            tab_inactive: s(dim),
+            status: Style::default().fg(text).bg(Color::Rgb(248, 248, 248)),
+            status_file: Style::default().fg(white).bg(accent),
+            status_info: s(dim),
+            status_checking: s(dim),
+            status_error: s(Color::Rgb(229, 20, 0)),
+            status_warning: s(Color::Rgb(191, 136, 3)),
+            status_ok: s(Color::Rgb(0, 128, 0)),
+            diag_location: s(dim),
+            tree_dir: s(list).add_modifier(Modifier::BOLD),
+            tree_file: s(text),
+            tree_selected_dir: Style::default()
+                .fg(white)
+                .bg(list)
+                .add_modifier(Modifier::BOLD),
+            tree_selected_file: Style::default().fg(text).bg(Color::Rgb(228, 230, 241)),
+            tree_icon: s(dim),
+            panel_focused: s(accent).add_modifier(Modifier::BOLD),
+            list_prompt: s(dim),
+            list_input: s(accent),
+            list_item: s(text),
+            list_selected: Style::default().fg(text).bg(Color::Rgb(228, 230, 241)),
+            list_current: s(accent).add_modifier(Modifier::BOLD),
+            list_match: s(accent).add_modifier(Modifier::BOLD),
+            list_kind: s(list),
+            list_detail: s(dim),
+            list_removed: s(Color::Rgb(229, 20, 0)),
+            list_added: s(Color::Rgb(0, 128, 0)),
+        };
+
+        Self {
+            name: "light-modern".to_string(),
+            styles,
+            default: s(text),
+            ui,
+        }
+    }
+
+    /// Load a theme by name: a built-in one, or `themes/<name>.toml` /
+    /// `themes/<name>.json` from the config directory.
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        Self::load_inherited(name, 0)
+    }
+
+    fn load_inherited(name: &str, depth: usize) -> anyhow::Result<Self> {
+        if depth > MAX_INHERITS {
+            bail!("theme {}: too many levels of inherits", name);
+        }
+        // Names are file stems in the themes directory, never paths.
+        if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+            bail!("invalid theme name: {}", name);
+        }
+        let Some((path, source)) = theme_file(name) else {
+            return Self::builtin(name).with_context(|| format!("no such theme: {}", name));
+        };
+
+        let file: ThemeFile = if path.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str(&source).with_context(|| format!("{}", path.display()))?
+        } else {
+            toml::from_str(&source).with_context(|| format!("{}", path.display()))?
+        };
+
+        let base = file.inherits.as_deref().unwrap_or("dark-modern");
+        // A file may shadow a built-in theme and still build on it.
+        let mut theme = match Self::builtin(base) {
+            Some(theme) if base == name => theme,
+            _ => Self::load_inherited(base, depth + 1)?,
+        };
+        theme.name = name.to_string();
+
+        for (key, def) in file.ui {
+            let style = def.to_style().with_context(|| format!("ui.{}", key))?;
+            if key == "text" {
+                theme.default = style;
+            } else {
+                *theme
+                    .ui
+                    .slot(&key)
+                    .with_context(|| format!("unknown ui colour: {}", key))? = style;
+            }
+        }
+        for (capture, def) in file.syntax {
+            let style = def
+                .to_style()
+                .with_context(|| format!("syntax.{}", capture))?;
+            theme.styles.insert(capture, style);
+        }
+        Ok(theme)
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark-modern" => Some(Self::vscode_dark_modern()),
+            "light-modern" => Some(Self::vscode_light_modern()),
+            _ => None,
+        }
+    }
+
+    /// Names of the built-in themes followed by those in the config directory.
+    pub fn available() -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_THEMES.iter().map(|n| n.to_string()).collect();
+        let dir = config::config_dir().map(|d| d.join("themes"));
+        if let Some(entries) = dir.and_then(|d| std::fs::read_dir(d).ok()) {
+            let mut files: Vec<String> = entries
+                .flatten()
+                .map(|e| PathBuf::from(e.file_name()))
+                .filter(|p| p.extension().is_some_and(|e| e == "toml" || e == "json"))
+                .filter_map(|p| Some(p.file_stem()?.to_string_lossy().to_string()))
+                .filter(|n| !names.contains(n))
+                .collect();
+            files.sort();
+            files.dedup();
+            names.extend(files);
+        }
+        names
+    }
+
+    /// Style for a capture name, falling back to its parent scopes:
+    /// `function.method.call` → `function.method` → `function`. Captures the
+    /// theme knows nothing about leave the text underneath unchanged.
+    pub fn style_for(&self, capture: &str) -> Style {
+        let mut name = capture;
+        loop {
+            if let Some(&style) = self.styles.get(name) {
+                return style;
+            }
+            match name.rfind('.') {
+                Some(dot) => name = &name[..dot],
+                None => return Style::default(),
+            }
+        }
+    }
+}
+
+fn theme_file(name: &str) -> Option<(PathBuf, String)> {
+    let dir = config::config_dir()?.join("themes");
+    ["toml", "json"].iter().find_map(|ext| {
+        let path = dir.join(format!("{}.{}", name, ext));
+        let source = std::fs::read_to_string(&path).ok()?;
+        Some((path, source))
+    })
+}
+
+/// On-disk theme layout, e.g. in TOML:
+///
+/// ```toml
+/// inherits = "light-modern"
+///
+/// [ui]
+/// gutter = "#999999"
+/// selection = { bg = "#cce4ff" }
+///
+/// [syntax]
+/// keyword = { fg = "#0000ff", modifiers = ["bold"] }
+/// "function.method" = "#795e26"
+/// ```
+#[derive(Deserialize)]
+struct ThemeFile {
+    inherits: Option<String>,
+    #[serde(default)]
+    ui: HashMap<String, StyleDef>,
+    #[serde(default)]
+    syntax: HashMap<String, StyleDef>,
+}
+
+/// A style is either just a foreground colour or a table of attributes.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StyleDef {
+    Fg(String),
+    Full {
+        fg: Option<String>,
+        bg: Option<String>,
+        #[serde(default)]
+        modifiers: Vec<String>,
+    },
+}
+
+impl StyleDef {
+    fn to_style(&self) -> anyhow::Result<Style> {
+        let color = |c: &str| Color::from_str(c).map_err(|_| anyhow::anyhow!("bad colour: {}", c));
+        match self {
+            Self::Fg(fg) => Ok(Style::default().fg(color(fg)?)),
+            Self::Full { fg, bg, modifiers } => {
+                let mut style = Style::default();
+                if let Some(fg) = fg {
+                    style = style.fg(color(fg)?);
+                }
+                if let Some(bg) = bg {
+                    style = style.bg(color(bg)?);
+                }
+                for m in modifiers {
+                    style = style.add_modifier(match m.as_str() {
+                        "bold" => Modifier::BOLD,
+                        "dim" => Modifier::DIM,
+                        "italic" => Modifier::ITALIC,
+                        "underlined" => Modifier::UNDERLINED,
+                        "reversed" => Modifier::REVERSED,
+                        "crossed_out" => Modifier::CROSSED_OUT,
+                        other => bail!("unknown modifier: {}", other),
+                    });
+                }
+                Ok(style)
+            }
+        }
+    }
+}
@@ -1,12 +1,14 @@
 use ratatui::{
     Frame,
     layout::Rect,
-    style::{Color, Modifier, Style},
+    style::Style,
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
 use std::path::{Path, PathBuf};
 
+use crate::theme::UiTheme;
+
 #[derive(Debug)]
 struct FileEntry {
     path: PathBuf,
@@ -17,6 +19,7 @@ struct FileEntry {
 }
 
 pub struct FileTree {
+    pub root: PathBuf,
     entries: Vec<FileEntry>,
     selected: usize,
     scroll: usize,
@@ -26,6 +29,7 @@ impl FileTree {
     pub fn new(root: &Path) -> Self {
         let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
         let mut tree = Self {
+            root: root.clone(),
             entries: Vec::new(),
             selected: 0,
             scroll: 0,
@@ -47,7 +51,7 @@ impl FileTree {
             let path = entry.path();
             let name = entry.file_name().to_string_lossy().to_string();
 
-            if name.starts_with('.') || name == "target" {
+            if is_excluded(&name) {
                 continue;
             }
 
@@ -67,10 +71,10 @@ impl FileTree {
             }
         }
 
-        dirs.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        files.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        dirs.sort_by_key(|a| a.name.to_lowercase());
+        files.sort_by_key(|a| a.name.to_lowercase());
 
-        for entry in dirs.into_iter().chain(files.into_iter()) {
+        for entry in dirs.into_iter().chain(files) {
             self.entries.push(entry);
         }
     }
@@ -118,7 +122,7 @@ impl FileTree {
         for entry in read_dir.flatten() {
             let path = entry.path();
             let name = entry.file_name().to_string_lossy().to_string();
-            if name.starts_with('.') || name == "target" || name == "node_modules" {
+            if is_excluded(&name) {
                 continue;
             }
             let is_dir = path.is_dir();
@@ -136,8 +140,8 @@ impl FileTree {
             }
         }
 
-        dirs.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        files.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        dirs.sort_by_key(|a| a.name.to_lowercase());
+        files.sort_by_key(|a| a.name.to_lowercase());
         children.extend(dirs);
         children.extend(files);
 
@@ -166,7 +170,7 @@ impl FileTree {
         }
     }
 
-    pub fn render(&self, f: &mut Frame, area: Rect) {
+    pub fn render(&self, f: &mut Frame, area: Rect, ui: &UiTheme) {
         let inner_height = area.height.saturating_sub(2) as usize;
 
         let scroll = if self.selected < self.scroll {
@@ -191,26 +195,16 @@ impl FileTree {
                     "  "
                 };
 
-                let name_style = if i == self.selected {
-                    if entry.is_dir {
-                        Style::default()
-                            .fg(Color::Black)
-                            .bg(Color::Cyan)
-                            .add_modifier(Modifier::BOLD)
-                    } else {
-                        Style::default().fg(Color::Black).bg(Color::White)
-                    }
-                } else if entry.is_dir {
-                    Style::default()
-                        .fg(Color::Cyan)
-                        .add_modifier(Modifier::BOLD)
-                } else {
-                    Style::default().fg(Color::White)
+                let name_style = match (i == self.selected, entry.is_dir) {
+                    (true, true) => ui.tree_selected_dir,
+                    (true, false) => ui.tree_selected_file,
+                    (false, true) => ui.tree_dir,
+                    (false, false) => ui.tree_file,
                 };
 
                 Line::from(vec![
                     Span::styled(indent, Style::default()),
-                    Span::styled(icon, Style::default().fg(Color::DarkGray)),
+                    Span::styled(icon, ui.tree_icon),
                     Span::styled(&entry.name, name_style),
                 ])
             })
@@ -219,7 +213,7 @@ impl FileTree {
         let block = Block::default()
             .title(" Files ")
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::DarkGray));
+            .border_style(ui.border);
 
         f.render_widget(Paragraph::new(lines).block(block), area);
     }
@@ -246,3 +240,52 @@ impl FileTree {
         }
     }
 }
+
+/// Entries hidden from the tree and skipped by project-wide walks.
+pub fn is_excluded(name: &str) -> bool {
+    name.starts_with('.') || name == "target" || name == "node_modules"
+}
+
+/// Every file under `root`, skipping excluded entries and symlinked
+/// directories, in sorted order.
+pub fn walk_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    walk_files_by_dir(root, |found| {
+        files.extend(found);
+        true
+    });
+    files.sort();
+    files
+}
+
+/// Walk the files under `root` like `walk_files`, handing `visit` the files
+/// of one directory at a time as they are read. The walk stops as soon as
+/// `visit` returns false.
+pub fn walk_files_by_dir(root: &Path, mut visit: impl FnMut(Vec<PathBuf>) -> bool) {
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let Ok(read_dir) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut files = Vec::new();
+        for entry in read_dir.flatten() {
+            if is_excluded(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                stack.push(path);
+            } else if !(file_type.is_symlink() && path.is_dir()) {
+                // Links to directories aren't followed, as they may loop.
+                files.push(path);
+            }
+        }
+        if !visit(files) {
+            return;
+        }
+    }
+}